@@ -4,6 +4,7 @@
 #[openbrush::contract]
 pub mod marketplace {
     use ink::env::DefaultEnvironment;
+    use ink::prelude::vec::Vec;
     use openbrush::contracts::ownable::*;
     use openbrush::contracts::psp34::Id;
     use openbrush::contracts::reentrancy_guard::*;
@@ -11,9 +12,406 @@ pub mod marketplace {
     
     use marketplace_pkg::{
         impls::marketplace::*,
+        impls::marketplace::nftmarketplace::MarketplaceEvents,
         traits::marketplace::*,
     };
 
+    #[ink(event)]
+    pub struct CollectionAdded {
+        #[ink(topic)]
+        collection: AccountId,
+        #[ink(topic)]
+        creator: AccountId,
+        royalty: u16,
+    }
+
+    #[ink(event)]
+    pub struct CollectionDeactivated {
+        #[ink(topic)]
+        collection: AccountId,
+        #[ink(topic)]
+        caller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct RoyaltySplitSet {
+        #[ink(topic)]
+        collection: AccountId,
+        #[ink(topic)]
+        caller: AccountId,
+        split: Vec<(AccountId, u16)>,
+    }
+
+    #[ink(event)]
+    pub struct CollectionRemoved {
+        #[ink(topic)]
+        collection: AccountId,
+        #[ink(topic)]
+        caller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct ItemRemoved {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OwnerSynced {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct KeeperRewarded {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        keeper: AccountId,
+        amount: Balance,
+        currency: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct ListingInvalidated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        seller: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct ItemRegistered {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        owner: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct Listed {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuctionCreated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+        buy_now: Option<Balance>,
+        min_bid: Balance,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct DutchAuctionCreated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        start_price: Balance,
+        end_price: Balance,
+        start_time: Timestamp,
+        duration: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct SealedAuctionCreated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        min_bid: Balance,
+        commit_end_time: Timestamp,
+        reveal_end_time: Timestamp,
+        vickrey: bool,
+    }
+
+    #[ink(event)]
+    pub struct BidCommitted {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        deposit: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BidRevealed {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BidPlaced {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: Balance,
+        next_min_bid: Balance,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct AuctionExtensionSet {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        extension_window: Timestamp,
+        extension_length: Timestamp,
+        max_end_time: Option<Timestamp>,
+    }
+
+    #[ink(event)]
+    pub struct Outbid {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        bidder: AccountId,
+        refund: Balance,
+    }
+
+    #[ink(event)]
+    pub struct Sold {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        listing_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: Balance,
+        seller_share: Balance,
+        royalties: Balance,
+        market_fee: Balance,
+        creator: AccountId,
+        currency: Option<AccountId>,
+    }
+
+    #[ink(event)]
+    pub struct AuctionSettled {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        seller: Option<AccountId>,
+        #[ink(topic)]
+        winner: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct ListingWithdrawn {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct DirectSaleUpdated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct AuctionUpdated {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        buy_now: Option<Balance>,
+        min_bid: Balance,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct StatusChanged {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        listing_id: u64,
+        status: types::ItemStatus,
+    }
+
+    #[ink(event)]
+    pub struct ListingExpired {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct OfferMade {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        offerer: AccountId,
+        amount: Balance,
+        expiry: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct OfferCancelled {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        offerer: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct OfferAccepted {
+        #[ink(topic)]
+        collection: AccountId,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        offerer: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CollectionOfferMade {
+        #[ink(topic)]
+        collection: AccountId,
+        offer_id: u64,
+        #[ink(topic)]
+        offerer: AccountId,
+        price: Balance,
+        quantity: u32,
+        expiry: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct CollectionOfferCancelled {
+        #[ink(topic)]
+        collection: AccountId,
+        offer_id: u64,
+        #[ink(topic)]
+        offerer: AccountId,
+        refund: Balance,
+    }
+
+    #[ink(event)]
+    pub struct CollectionOfferFilled {
+        #[ink(topic)]
+        collection: AccountId,
+        offer_id: u64,
+        token_id: Id,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        offerer: AccountId,
+        price: Balance,
+        remaining: u32,
+    }
+
+    #[ink(event)]
+    pub struct BundleCreated {
+        #[ink(topic)]
+        bundle_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+        items: Vec<(AccountId, Id)>,
+        price: Balance,
+        min_bid: Balance,
+        end_time: Timestamp,
+    }
+
+    #[ink(event)]
+    pub struct BundleBidPlaced {
+        #[ink(topic)]
+        bundle_id: u64,
+        #[ink(topic)]
+        bidder: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BundleSold {
+        #[ink(topic)]
+        bundle_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+        #[ink(topic)]
+        buyer: AccountId,
+        price: Balance,
+    }
+
+    #[ink(event)]
+    pub struct BundleCancelled {
+        #[ink(topic)]
+        bundle_id: u64,
+        #[ink(topic)]
+        seller: AccountId,
+    }
+
+    #[ink(event)]
+    pub struct FundsWithdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        amount: Balance,
+    }
+
+    #[ink(event)]
+    pub struct TokenFundsWithdrawn {
+        #[ink(topic)]
+        account: AccountId,
+        #[ink(topic)]
+        currency: AccountId,
+        amount: Balance,
+    }
+
     #[ink(storage)]
     #[derive(Default, Storage)]
     pub struct MarketplaceContract {
@@ -31,6 +429,9 @@ pub mod marketplace {
             
                 let mut instance = Self::default();
                 instance.marketplace.fee = 100; // 1%
+                instance.marketplace.unrevealed_penalty = 1000; // 10%
+                instance.marketplace.max_royalty = 1000; // 10%
+                instance.marketplace.reserve_grace_period = 86_400_000; // 1 day
                 instance.marketplace.market_fee_recipient = market_fee_recipient;
 
                 let caller = instance.env().caller();
@@ -42,6 +443,212 @@ pub mod marketplace {
 
     impl NFTMarketplace for MarketplaceContract {}
 
+    impl MarketplaceEvents for MarketplaceContract {
+        fn emit_collection_added_event(&self, collection: AccountId, creator: AccountId, royalty: u16) {
+            self.env().emit_event(CollectionAdded { collection, creator, royalty });
+        }
+
+        fn emit_item_registered_event(&self, collection: AccountId, token_id: Id, owner: AccountId) {
+            self.env().emit_event(ItemRegistered { collection, token_id, owner });
+        }
+
+        fn emit_listed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, price: Balance) {
+            self.env().emit_event(Listed { collection, token_id, listing_id, seller, price });
+        }
+
+        fn emit_auction_created_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, buy_now: Option<Balance>, min_bid: Balance, end_time: Timestamp) {
+            self.env().emit_event(AuctionCreated { collection, token_id, listing_id, seller, buy_now, min_bid, end_time });
+        }
+
+        fn emit_dutch_auction_created_event(&self, collection: AccountId, token_id: Id, seller: AccountId, start_price: Balance, end_price: Balance, start_time: Timestamp, duration: Timestamp) {
+            self.env().emit_event(DutchAuctionCreated { collection, token_id, seller, start_price, end_price, start_time, duration });
+        }
+
+        fn emit_sealed_auction_created_event(&self, collection: AccountId, token_id: Id, seller: AccountId, min_bid: Balance, commit_end_time: Timestamp, reveal_end_time: Timestamp, vickrey: bool) {
+            self.env().emit_event(SealedAuctionCreated { collection, token_id, seller, min_bid, commit_end_time, reveal_end_time, vickrey });
+        }
+
+        fn emit_bid_committed_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, deposit: Balance) {
+            self.env().emit_event(BidCommitted { collection, token_id, bidder, deposit });
+        }
+
+        fn emit_bid_revealed_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, amount: Balance) {
+            self.env().emit_event(BidRevealed { collection, token_id, bidder, amount });
+        }
+
+        fn emit_bid_placed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, bidder: AccountId, amount: Balance, next_min_bid: Balance, end_time: Timestamp) {
+            self.env().emit_event(BidPlaced { collection, token_id, listing_id, bidder, amount, next_min_bid, end_time });
+        }
+
+        fn emit_auction_extension_set_event(&self, collection: AccountId, token_id: Id, extension_window: Timestamp, extension_length: Timestamp, max_end_time: Option<Timestamp>) {
+            self.env().emit_event(AuctionExtensionSet { collection, token_id, extension_window, extension_length, max_end_time });
+        }
+
+        fn emit_outbid_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, refund: Balance) {
+            self.env().emit_event(Outbid { collection, token_id, bidder, refund });
+        }
+
+        fn emit_sold_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, buyer: AccountId, price: Balance,
+            seller_share: Balance, royalties: Balance, market_fee: Balance, creator: AccountId, currency: Option<AccountId>) {
+            self.env().emit_event(Sold { collection, token_id, listing_id, seller, buyer, price, seller_share, royalties, market_fee, creator, currency });
+        }
+
+        fn emit_auction_settled_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: Option<AccountId>, winner: AccountId, price: Balance) {
+            self.env().emit_event(AuctionSettled { collection, token_id, listing_id, seller, winner, price });
+        }
+
+        fn emit_listing_withdrawn_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId) {
+            self.env().emit_event(ListingWithdrawn { collection, token_id, listing_id, seller });
+        }
+
+        fn emit_listing_expired_event(&self, collection: AccountId, token_id: Id, seller: AccountId) {
+            self.env().emit_event(ListingExpired { collection, token_id, seller });
+        }
+
+        fn emit_royalty_split_set_event(&self, collection: AccountId, caller: AccountId, split: Vec<(AccountId, u16)>) {
+            self.env().emit_event(RoyaltySplitSet { collection, caller, split });
+        }
+
+        fn emit_collection_deactivated_event(&self, collection: AccountId, caller: AccountId) {
+            self.env().emit_event(CollectionDeactivated { collection, caller });
+        }
+
+        fn emit_collection_removed_event(&self, collection: AccountId, caller: AccountId) {
+            self.env().emit_event(CollectionRemoved { collection, caller });
+        }
+
+        fn emit_item_removed_event(&self, collection: AccountId, token_id: Id, owner: AccountId) {
+            self.env().emit_event(ItemRemoved { collection, token_id, owner });
+        }
+
+        fn emit_owner_synced_event(&self, collection: AccountId, token_id: Id, owner: AccountId) {
+            self.env().emit_event(OwnerSynced { collection, token_id, owner });
+        }
+
+        fn emit_keeper_rewarded_event(&self, collection: AccountId, token_id: Id, keeper: AccountId, amount: Balance, currency: Option<AccountId>) {
+            self.env().emit_event(KeeperRewarded { collection, token_id, keeper, amount, currency });
+        }
+
+        fn emit_listing_invalidated_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: Option<AccountId>) {
+            self.env().emit_event(ListingInvalidated { collection, token_id, listing_id, seller });
+        }
+
+        fn emit_status_changed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, status: types::ItemStatus) {
+            self.env().emit_event(StatusChanged { collection, token_id, listing_id, status });
+        }
+
+        fn emit_direct_sale_updated_event(&self, collection: AccountId, token_id: Id, seller: AccountId, price: Balance) {
+            self.env().emit_event(DirectSaleUpdated { collection, token_id, seller, price });
+        }
+
+        fn emit_auction_updated_event(&self, collection: AccountId, token_id: Id, seller: AccountId, buy_now: Option<Balance>,
+            min_bid: Balance, end_time: Timestamp) {
+            self.env().emit_event(AuctionUpdated { collection, token_id, seller, buy_now, min_bid, end_time });
+        }
+
+        fn emit_funds_withdrawn_event(&self, account: AccountId, amount: Balance) {
+            self.env().emit_event(FundsWithdrawn { account, amount });
+        }
+
+        fn emit_token_funds_withdrawn_event(&self, account: AccountId, currency: AccountId, amount: Balance) {
+            self.env().emit_event(TokenFundsWithdrawn { account, currency, amount });
+        }
+
+        fn emit_bundle_created_event(&self, bundle_id: u64, seller: AccountId, items: Vec<(AccountId, Id)>, price: Balance, min_bid: Balance, end_time: Timestamp) {
+            self.env().emit_event(BundleCreated { bundle_id, seller, items, price, min_bid, end_time });
+        }
+
+        fn emit_bundle_bid_placed_event(&self, bundle_id: u64, bidder: AccountId, amount: Balance) {
+            self.env().emit_event(BundleBidPlaced { bundle_id, bidder, amount });
+        }
+
+        fn emit_bundle_sold_event(&self, bundle_id: u64, seller: AccountId, buyer: AccountId, price: Balance) {
+            self.env().emit_event(BundleSold { bundle_id, seller, buyer, price });
+        }
+
+        fn emit_bundle_cancelled_event(&self, bundle_id: u64, seller: AccountId) {
+            self.env().emit_event(BundleCancelled { bundle_id, seller });
+        }
+
+        fn emit_offer_made_event(&self, collection: AccountId, token_id: Id, offerer: AccountId, amount: Balance, expiry: Timestamp) {
+            self.env().emit_event(OfferMade { collection, token_id, offerer, amount, expiry });
+        }
+
+        fn emit_offer_cancelled_event(&self, collection: AccountId, token_id: Id, offerer: AccountId, amount: Balance) {
+            self.env().emit_event(OfferCancelled { collection, token_id, offerer, amount });
+        }
+
+        fn emit_offer_accepted_event(&self, collection: AccountId, token_id: Id, seller: AccountId, offerer: AccountId, amount: Balance) {
+            self.env().emit_event(OfferAccepted { collection, token_id, seller, offerer, amount });
+        }
+
+        fn emit_collection_offer_made_event(&self, collection: AccountId, offer_id: u64, offerer: AccountId, price: Balance, quantity: u32, expiry: Timestamp) {
+            self.env().emit_event(CollectionOfferMade { collection, offer_id, offerer, price, quantity, expiry });
+        }
+
+        fn emit_collection_offer_cancelled_event(&self, collection: AccountId, offer_id: u64, offerer: AccountId, refund: Balance) {
+            self.env().emit_event(CollectionOfferCancelled { collection, offer_id, offerer, refund });
+        }
+
+        fn emit_collection_offer_filled_event(&self, collection: AccountId, offer_id: u64, token_id: Id, seller: AccountId, offerer: AccountId, price: Balance, remaining: u32) {
+            self.env().emit_event(CollectionOfferFilled { collection, offer_id, token_id, seller, offerer, price, remaining });
+        }
+    }
+
+    // The off-chain test environment cannot call into collections. Tokens belong to the caller
+    // until they are transferred, and transfers are kept in a per-test ledger. Like a collection,
+    // the ledger refuses to move a token held by anyone but the caller or the marketplace.
+    // PSP22 payments are drawn from a second ledger of currency balances. The marketplace is
+    // approved for every token unless a test revokes it.
+    #[cfg(test)]
+    thread_local! {
+        static TOKEN_OWNERS: core::cell::RefCell<Vec<((AccountId, Id), AccountId)>> = Default::default();
+        static CURRENCY_BALANCES: core::cell::RefCell<Vec<((AccountId, AccountId), Balance)>> = Default::default();
+        static REVOKED_APPROVALS: core::cell::RefCell<Vec<(AccountId, Id)>> = Default::default();
+    }
+
+    #[cfg(test)]
+    impl nftmarketplace::Internal for MarketplaceContract {
+        fn get_token_owner(&self, address: AccountId, token_id: Id) -> Option<AccountId> {
+            TOKEN_OWNERS.with(|owners| owners.borrow().iter()
+                .find(|(key, _)| *key == (address, token_id.clone()))
+                .map(|(_, owner)| *owner))
+                .or(Some(self.env().caller()))
+        }
+
+        fn transfer_token(&mut self, address: AccountId, to: AccountId, token_id: Id) -> Result<(), openbrush::contracts::psp34::PSP34Error> {
+            let owner = self.get_token_owner(address, token_id.clone());
+            if owner != Some(self.env().caller()) && owner != Some(self.env().account_id()) {
+                return Err(openbrush::contracts::psp34::PSP34Error::NotApproved)
+            }
+            TOKEN_OWNERS.with(|owners| {
+                let mut owners = owners.borrow_mut();
+                owners.retain(|(key, _)| *key != (address, token_id.clone()));
+                owners.push(((address, token_id), to));
+            });
+            Ok(())
+        }
+
+        fn receive_payment(&mut self, currency: AccountId, from: AccountId, amount: Balance) -> Result<(), types::MarketplaceError> {
+            CURRENCY_BALANCES.with(|balances| {
+                let mut balances = balances.borrow_mut();
+                let entry = balances.iter_mut()
+                    .find(|(key, balance)| *key == (currency, from) && *balance >= amount)
+                    .ok_or(types::MarketplaceError::PaymentFailed)?;
+                entry.1 -= amount;
+                Ok(())
+            })
+        }
+
+        fn is_approved(&self, address: AccountId, token_id: Id, _owner: AccountId) -> bool {
+            REVOKED_APPROVALS.with(|revoked| !revoked.borrow().contains(&(address, token_id)))
+        }
+
+        fn get_royalty_info(&self, _address: AccountId, _token_id: Id, _sales_price: Balance) -> Option<(AccountId, Balance)> {
+            None
+        }
+    }
+
     #[cfg(test)]
     mod Tests {
         use super::*;
@@ -51,7 +658,16 @@ pub mod marketplace {
             contracts::psp34::Id,
             traits::String,
         };
-        use marketplace_pkg::impls::marketplace::types::MarketplaceError;
+        use marketplace_pkg::impls::marketplace::types::{
+            MarketplaceError,
+            Offer,
+            CollectionOffer,
+            DutchAuction,
+            ItemIndex,
+            ItemStatus,
+            ListingHealth,
+        };
+        use marketplace_pkg::impls::marketplace::nftmarketplace::Internal;
 
         #[ink::test]
         fn new_works() {
@@ -60,12 +676,233 @@ pub mod marketplace {
             assert_eq!(marketplace.get_fee_recipient(), fee_recipient());
         }
 
+        #[ink::test]
+        fn unrevealed_penalty_works() {
+            let mut marketplace = init_contract();
+            assert_eq!(marketplace.get_unrevealed_penalty(), 1000);
+            assert_eq!(marketplace.set_unrevealed_penalty(10001), Err(MarketplaceError::IneligiblePenalty));
+            assert!(marketplace.set_unrevealed_penalty(500).is_ok());
+            assert_eq!(marketplace.get_unrevealed_penalty(), 500);
+        }
+
+        #[ink::test]
+        fn accepted_currencies_work() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            assert_eq!(marketplace.get_accepted_currencies(), vec![]);
+            assert!(marketplace.add_currency(accounts.django).is_ok());
+            assert_eq!(marketplace.add_currency(accounts.django), Err(MarketplaceError::CurrencyAlreadyAccepted));
+            assert_eq!(marketplace.get_accepted_currencies(), vec![accounts.django]);
+            assert!(marketplace.remove_currency(accounts.django).is_ok());
+            assert_eq!(marketplace.remove_currency(accounts.django), Err(MarketplaceError::CurrencyNotAccepted));
+            assert_eq!(marketplace.get_token_liabilities(accounts.django), 0);
+        }
+
+        #[ink::test]
+        fn token_auction_rejects_wrong_payment() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            let currency = accounts.django;
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(
+                marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,50,Some(currency)),
+                Err(MarketplaceError::CurrencyNotAccepted)
+            );
+            assert!(marketplace.add_currency(currency).is_ok());
+            assert_eq!(marketplace.add_currency(currency), Err(MarketplaceError::CurrencyAlreadyAccepted));
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,50,Some(currency)).is_ok());
+
+            // Token auctions take neither native bids nor native value alongside a token bid
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(100);
+            assert_eq!(marketplace.make_bid(contract_address(),Id::U64(1)), Err(MarketplaceError::CurrencyMismatch));
+            assert_eq!(marketplace.make_token_bid(contract_address(),Id::U64(1),100), Err(MarketplaceError::CurrencyMismatch));
+
+            test::set_value_transferred::<Environment>(0);
+            assert_eq!(marketplace.make_token_bid(contract_address(),Id::U64(1),100), Err(MarketplaceError::PaymentFailed));
+            fund_currency(currency, accounts.bob, 100);
+            assert!(marketplace.make_token_bid(contract_address(),Id::U64(1),100).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().highest_bidder, Some(accounts.bob));
+        }
+
+        #[ink::test]
+        fn keeper_reward_works() {
+            let mut marketplace = init_contract();
+            assert_eq!(marketplace.get_keeper_reward(), 0);
+            assert_eq!(marketplace.set_keeper_reward(10001), Err(MarketplaceError::IneligibleKeeperReward));
+            assert!(marketplace.set_keeper_reward(2000).is_ok());
+            assert_eq!(marketplace.get_keeper_reward(), 2000);
+            assert_eq!(marketplace.get_settleable_auctions(0, 10), vec![]);
+            assert_eq!(marketplace.settle_auction(contract_address(), Id::U8(1)), Err(MarketplaceError::TokenDoesNotExist));
+        }
+
+        #[ink::test]
+        fn clear_expired_listing_requires_listing() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            assert!(!marketplace.is_listing_expired(accounts.django, Id::U8(1)));
+            assert_eq!(marketplace.clear_expired_listing(accounts.django, Id::U8(1)), Err(MarketplaceError::TokenDoesNotExist));
+            assert_eq!(marketplace.listing_health(accounts.django, Id::U8(1)), ListingHealth::NotListed);
+        }
+
+        #[ink::test]
+        fn expired_listing_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(
+                marketplace.create_direct_sale(contract_address(),Id::U64(1),1000,None,Some(0)),
+                Err(MarketplaceError::IneligibleListingExpiry)
+            );
+            assert!(marketplace.create_direct_sale(contract_address(),Id::U64(1),1000,None,Some(10)).is_ok());
+            assert_eq!(marketplace.clear_expired_listing(contract_address(),Id::U64(1)), Err(MarketplaceError::ListingNotExpired));
+
+            // At 12 the listing can no longer be bought, and anyone may clear it
+            test::advance_block::<Environment>();
+            test::advance_block::<Environment>();
+            assert!(marketplace.is_listing_expired(contract_address(),Id::U64(1)));
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1000);
+            assert_eq!(marketplace.close_direct_sale(contract_address(),Id::U64(1)), Err(MarketplaceError::ListingExpired));
+
+            assert!(marketplace.clear_expired_listing(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.alice));
+        }
+
+        #[ink::test]
+        fn update_listing_requires_listing() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            assert_eq!(marketplace.update_direct_sale_price(accounts.django, Id::U8(1), 10), Err(MarketplaceError::TokenDoesNotExist));
+            assert_eq!(marketplace.update_auction_params(accounts.django, Id::U8(1), None, 10, 100), Err(MarketplaceError::TokenDoesNotExist));
+        }
+
+        #[ink::test]
+        fn update_auction_params_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),Some(1000),100,0,false,50,None).is_ok());
+
+            set_sender(accounts.bob);
+            assert_eq!(
+                marketplace.update_auction_params(contract_address(),Id::U64(1),None,50,100),
+                Err(MarketplaceError::NotTheOwner)
+            );
+
+            set_sender(accounts.alice);
+            assert!(marketplace.update_auction_params(contract_address(),Id::U64(1),Some(2000),200,100).is_ok());
+            let item = marketplace.get_item(contract_address(),Id::U64(1)).unwrap();
+            assert_eq!(item.buy_now, Some(2000));
+            assert_eq!(item.min_bid, 200);
+            assert_eq!(item.next_min_bid, 200);
+            assert_eq!(item.bid_end_time, 100);
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(200);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+
+            // After the first bid no term can change, including a later end time
+            set_sender(accounts.alice);
+            assert_eq!(
+                marketplace.update_auction_params(contract_address(),Id::U64(1),Some(3000),300,100),
+                Err(MarketplaceError::AuctionHasBids)
+            );
+            assert_eq!(
+                marketplace.update_auction_params(contract_address(),Id::U64(1),None,100,1000),
+                Err(MarketplaceError::AuctionHasBids)
+            );
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().bid_end_time, 100);
+        }
+
+        #[ink::test]
+        fn auction_extension_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,10,None).is_ok());
+            assert_eq!(
+                marketplace.set_auction_extension(contract_address(),Id::U64(1),5,8,Some(9)),
+                Err(MarketplaceError::IneligibleBidDuration)
+            );
+            assert!(marketplace.set_auction_extension(contract_address(),Id::U64(1),5,8,Some(20)).is_ok());
+
+            // A bid outside the window leaves the end where it was
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(100);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().bid_end_time, 10);
+
+            // At 6 a bid inside the window pushes the end to 14
+            test::advance_block::<Environment>();
+            set_sender(accounts.charlie);
+            test::set_value_transferred::<Environment>(200);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().bid_end_time, 14);
+
+            // At 12, past the original end, bidding is still open and the hard cap applies
+            test::advance_block::<Environment>();
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(400);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().bid_end_time, 20);
+
+            test::advance_block::<Environment>();
+            set_sender(accounts.charlie);
+            test::set_value_transferred::<Environment>(800);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).unwrap().bid_end_time, 20);
+            assert_eq!(marketplace.settle_auction(contract_address(),Id::U64(1)), Err(MarketplaceError::AuctionOngoing));
+
+            test::advance_block::<Environment>();
+            assert!(marketplace.settle_auction(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.charlie));
+        }
+
+        #[ink::test]
+        fn sealed_auction_cannot_be_extended() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_sealed_auction(contract_address(),Id::U64(1),100,3,3,false,10).is_ok());
+            assert_eq!(
+                marketplace.set_auction_extension(contract_address(),Id::U64(1),5,8,None),
+                Err(MarketplaceError::SealedBidAuction)
+            );
+        }
+
+        #[ink::test]
+        fn compute_bid_commitment_binds_bidder_and_amount() {
+            let marketplace = init_contract();
+            let accounts = default_accounts();
+            let commitment = marketplace.compute_bid_commitment(accounts.bob, 100, [7; 32]);
+
+            assert_eq!(commitment, marketplace.compute_bid_commitment(accounts.bob, 100, [7; 32]));
+            assert_ne!(commitment, marketplace.compute_bid_commitment(accounts.charlie, 100, [7; 32]));
+            assert_ne!(commitment, marketplace.compute_bid_commitment(accounts.bob, 101, [7; 32]));
+        }
+
         #[ink::test]
         fn set_marketplacefees_works() {
             let mut marketplace = init_contract();
             assert_eq!(marketplace.get_marketplace_fee(), 100);
             assert!(marketplace.set_marketplace_fee(200).is_ok());
             assert_eq!(marketplace.get_marketplace_fee(), 200);
+            // The default maximum royalty is 10%, leaving at most 90% for the fee
+            assert_eq!(marketplace.set_marketplace_fee(9001), Err(MarketplaceError::FeeTooHigh));
+            assert!(marketplace.set_marketplace_fee(9000).is_ok());
+            assert_eq!(marketplace.set_max_royalty(1001), Err(MarketplaceError::RoyaltyTooHigh));
         }
 
         #[ink::test]
@@ -83,6 +920,7 @@ pub mod marketplace {
             assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
 
             assert_eq!(marketplace.get_collection_count(),1);
+            assert_eq!(test::recorded_events().count(), 1);
         }
 
         #[ink::test]
@@ -96,7 +934,11 @@ pub mod marketplace {
 
             assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
             assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
-            assert_eq!(marketplace.get_all_market_items(),vec![(contract_address(),Id::U64(3))]);
+            assert_eq!(marketplace.get_market_items(0,10),vec![(contract_address(),Id::U64(3))]);
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(3)), Some(ItemStatus::Unlisted));
+            assert!(marketplace.sync_owner(contract_address(),Id::U64(3)).is_ok());
+            assert_eq!(marketplace.sync_owner(contract_address(),Id::U64(4)), Err(MarketplaceError::TokenDoesNotExist));
+            assert_eq!(test::recorded_events().count(), 2);
         }
 
         #[ink::test]
@@ -111,7 +953,282 @@ pub mod marketplace {
             assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
             assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
             assert!(marketplace.create_market_item(contract_address(),Id::U64(4)).is_ok());
-            assert_eq!(marketplace.get_all_market_items(),vec![(contract_address(),Id::U64(3)),(contract_address(),Id::U64(4))]);
+            assert_eq!(marketplace.get_market_items(0,10),vec![(contract_address(),Id::U64(3)),(contract_address(),Id::U64(4))]);
+            assert_eq!(marketplace.get_market_items(1,10),vec![(contract_address(),Id::U64(4))]);
+            assert_eq!(marketplace.get_collection_items(contract_address(),0,1),vec![(contract_address(),Id::U64(3))]);
+            assert_eq!(marketplace.get_index_length(ItemIndex::Collection(contract_address())), 2);
+            assert_eq!(marketplace.get_active_items(0,10),vec![]);
+        }
+
+        #[ink::test]
+        fn direct_sale_escrow_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_direct_sale(contract_address(),Id::U64(1),1000,None,None).is_ok());
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::DirectSale));
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![(contract_address(),Id::U64(1))]);
+            assert_eq!(marketplace.listing_health(contract_address(),Id::U64(1)), ListingHealth::Healthy);
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.withdraw_auction(contract_address(),Id::U64(1)), Err(MarketplaceError::NotTheOwner));
+
+            set_sender(accounts.alice);
+            assert!(marketplace.withdraw_auction(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![]);
+            assert_eq!(marketplace.get_active_items(0,10), vec![]);
+        }
+
+        #[ink::test]
+        fn listing_requires_approval_and_token() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            for token in [1u64, 2u64] {
+                assert!(marketplace.create_market_item(contract_address(),Id::U64(token)).is_ok());
+            }
+
+            revoke_approval(contract_address(), Id::U64(1));
+            assert_eq!(
+                marketplace.create_direct_sale(contract_address(),Id::U64(1),1000,None,None),
+                Err(MarketplaceError::ApprovalFailed)
+            );
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+
+            // A token that leaves escrow can no longer be bought
+            assert!(marketplace.create_direct_sale(contract_address(),Id::U64(2),1000,None,None).is_ok());
+            move_token(contract_address(), Id::U64(2), accounts.django);
+            assert_eq!(marketplace.listing_health(contract_address(),Id::U64(2)), ListingHealth::TokenMoved);
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1000);
+            assert_eq!(marketplace.close_direct_sale(contract_address(),Id::U64(2)), Err(MarketplaceError::ListingInvalid));
+        }
+
+        #[ink::test]
+        fn batch_buy_best_effort_skips_failed_items() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 10000);
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            for (token, price) in [(1u64, 1000), (2u64, 500)] {
+                assert!(marketplace.create_market_item(contract_address(),Id::U64(token)).is_ok());
+                assert!(marketplace.create_direct_sale(contract_address(),Id::U64(token),price,None,None).is_ok());
+            }
+
+            // The seller cannot buy their own listing, and nothing is paid or moved for it
+            assert_eq!(
+                marketplace.batch_buy(vec![(contract_address(),Id::U64(1))], false),
+                Ok(vec![Err(MarketplaceError::NotAuthorized)])
+            );
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::DirectSale));
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 0);
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1200);
+            let items = vec![
+                (contract_address(),Id::U64(1)),
+                (contract_address(),Id::U64(3)),
+                (contract_address(),Id::U64(2)),
+            ];
+            assert_eq!(
+                marketplace.batch_buy(items, false),
+                Ok(vec![Ok(()), Err(MarketplaceError::TokenDoesNotExist), Err(MarketplaceError::IneligibleBuyPrice)])
+            );
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.bob));
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(2)), Some(ItemStatus::DirectSale));
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![(contract_address(),Id::U64(2))]);
+            // Seller share and royalty both go to alice, who registered the collection
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 990);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 10);
+        }
+
+        #[ink::test]
+        fn settle_auction_delivers_to_highest_bidder() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.set_keeper_reward(5000).is_ok());
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,5,None).is_ok());
+
+            test::set_value_transferred::<Environment>(200);
+            assert_eq!(marketplace.make_bid(contract_address(),Id::U64(1)), Err(MarketplaceError::NotAuthorized));
+            set_sender(accounts.bob);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+
+            set_sender(accounts.charlie);
+            assert_eq!(marketplace.settle_auction(contract_address(),Id::U64(1)), Err(MarketplaceError::AuctionOngoing));
+            assert_eq!(marketplace.get_settleable_auctions(0,10), vec![]);
+            test::advance_block::<Environment>();
+            assert_eq!(marketplace.get_settleable_auctions(0,10), vec![(contract_address(),Id::U64(1))]);
+
+            // Whoever settles, the token goes to the highest bidder and the keeper earns half the market fee
+            assert!(marketplace.settle_auction(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.bob));
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 198);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.charlie), 1);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 1);
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![]);
+        }
+
+        #[ink::test]
+        fn settle_auction_releases_bid_below_reserve() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert_eq!(marketplace.get_reserve_grace_period(), 86_400_000);
+            assert!(marketplace.set_reserve_grace_period(10).is_ok());
+            assert!(marketplace.set_keeper_reward(5000).is_ok());
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,500,false,5,None).is_ok());
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(200);
+            assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+
+            // Within the grace period, which runs until 15, only the seller can decide what happens to the bid
+            set_sender(accounts.charlie);
+            for _ in 0..2 {
+                test::advance_block::<Environment>();
+                assert_eq!(marketplace.settle_auction(contract_address(),Id::U64(1)), Err(MarketplaceError::ReserveNotMet));
+                assert_eq!(marketplace.get_settleable_auctions(0,10), vec![]);
+            }
+
+            // Closing the auction after the grace period earns no keeper reward, as nothing is sold
+            test::advance_block::<Environment>();
+            assert_eq!(marketplace.get_settleable_auctions(0,10), vec![(contract_address(),Id::U64(1))]);
+            assert!(marketplace.settle_auction(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.charlie), 0);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 0);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 200);
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.alice));
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+        }
+
+        #[ink::test]
+        fn settle_sealed_auction_without_winner_releases_deposits() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_sealed_auction(contract_address(),Id::U64(1),100,3,3,false,10).is_ok());
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(300);
+            assert!(marketplace.commit_bid(contract_address(),Id::U64(1),[0u8; 32]).is_ok());
+            test::advance_block::<Environment>();
+            test::advance_block::<Environment>();
+
+            // Bob never revealed, so he forfeits the unrevealed penalty to the seller
+            set_sender(accounts.charlie);
+            assert!(marketplace.settle_auction(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 270);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 30);
+            assert_eq!(marketplace.get_sealed_bid(contract_address(),Id::U64(1),accounts.bob), None);
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.alice));
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Unlisted));
+        }
+
+        #[ink::test]
+        fn sealed_vickrey_auction_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert_eq!(
+                marketplace.create_sealed_auction(contract_address(),Id::U64(1),100,3,10,true,101),
+                Err(MarketplaceError::IneligibleBidLimit)
+            );
+            assert!(marketplace.create_sealed_auction(contract_address(),Id::U64(1),100,3,10,true,3).is_ok());
+
+            let bids = [(accounts.bob, 300, 400), (accounts.charlie, 200, 200), (accounts.django, 150, 150)];
+            for (bidder, amount, deposit) in bids {
+                set_sender(bidder);
+                test::set_value_transferred::<Environment>(deposit);
+                let commitment = marketplace.compute_bid_commitment(bidder, amount, [7; 32]);
+                assert!(marketplace.commit_bid(contract_address(),Id::U64(1),commitment).is_ok());
+            }
+            set_sender(accounts.eve);
+            test::set_value_transferred::<Environment>(500);
+            assert_eq!(marketplace.commit_bid(contract_address(),Id::U64(1),[0; 32]), Err(MarketplaceError::TooManyBids));
+
+            // Raising the penalty later does not reach bidders that already committed
+            set_sender(accounts.alice);
+            assert!(marketplace.set_unrevealed_penalty(5000).is_ok());
+
+            test::advance_block::<Environment>();
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.reveal_bid(contract_address(),Id::U64(1),300,[8; 32]), Err(MarketplaceError::InvalidReveal));
+            assert!(marketplace.reveal_bid(contract_address(),Id::U64(1),300,[7; 32]).is_ok());
+            set_sender(accounts.charlie);
+            assert!(marketplace.reveal_bid(contract_address(),Id::U64(1),200,[7; 32]).is_ok());
+
+            test::advance_block::<Environment>();
+            test::advance_block::<Environment>();
+            set_sender(accounts.eve);
+            assert!(marketplace.settle_auction(contract_address(),Id::U64(1)).is_ok());
+
+            // Bob wins at charlie's price of 200 and django forfeits 10% of his deposit
+            assert_eq!(marketplace.get_item(contract_address(),Id::U64(1)).map(|item| item.owner), Some(accounts.bob));
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 200);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.charlie), 200);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.django), 135);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 213);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 2);
+        }
+
+        #[ink::test]
+        fn remove_market_item_and_collection_works() {
+            let mut marketplace = init_contract();
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,150).is_ok());
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
+            assert_eq!(marketplace.remove_collection(contract_address()), Err(MarketplaceError::CollectionHasItems));
+
+            assert!(marketplace.remove_market_item(contract_address(),Id::U64(3)).is_ok());
+            assert_eq!(marketplace.get_item_count(), 0);
+            assert_eq!(marketplace.get_market_items(0,10), vec![]);
+
+            assert!(marketplace.deactivate_collection(contract_address()).is_ok());
+            assert_eq!(marketplace.create_market_item(contract_address(),Id::U64(4)), Err(MarketplaceError::CollectionInactive));
+
+            assert!(marketplace.remove_collection(contract_address()).is_ok());
+            assert!(marketplace.get_collection(contract_address()).is_none());
+            assert_eq!(marketplace.get_collection_count(), 0);
+        }
+
+        #[ink::test]
+        fn remove_collection_updates_creator_collections() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            for address in [contract_address(), accounts.django] {
+                assert!(marketplace.add_collection(address,String::from("Test"),String::from("TST"),String::from("https://ipfs.io/aaa"),150).is_ok());
+            }
+            // create_collection cannot instantiate contracts off-chain, so its bookkeeping is set up directly
+            marketplace.marketplace.creator_collections.insert(&accounts.alice, &vec![contract_address(), accounts.django]);
+            assert_eq!(marketplace.get_creator_collections(accounts.alice), vec![contract_address(), accounts.django]);
+
+            assert!(marketplace.remove_collection(contract_address()).is_ok());
+            assert_eq!(marketplace.get_creator_collections(accounts.alice), vec![accounts.django]);
+            assert!(marketplace.remove_collection(accounts.django).is_ok());
+            assert_eq!(marketplace.get_creator_collections(accounts.alice), vec![]);
         }
 
         #[ink::test]
@@ -137,6 +1254,508 @@ pub mod marketplace {
             assert_eq!(marketplace.get_blocknumber(),0);
         }
 
+        #[ink::test]
+        fn withdraw_funds_without_balance_fails() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 0);
+            assert_eq!(marketplace.get_total_liabilities(), 0);
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.withdraw_funds(), Err(MarketplaceError::NoFundsToWithdraw));
+        }
+
+        #[ink::test]
+        fn withdraw_funds_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,50,None).is_ok());
+            for (bidder, bid) in [(accounts.bob, 100), (accounts.charlie, 200)] {
+                set_sender(bidder);
+                test::set_value_transferred::<Environment>(bid);
+                assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            }
+
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 10000);
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 100);
+            assert_eq!(marketplace.withdraw_funds(), Ok(100));
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 0);
+            assert_eq!(marketplace.withdraw_funds(), Err(MarketplaceError::NoFundsToWithdraw));
+        }
+
+        #[ink::test]
+        fn withdraw_funds_fails_when_transfer_fails() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_auction(contract_address(),Id::U64(1),None,100,0,false,50,None).is_ok());
+            for (bidder, bid) in [(accounts.bob, 100), (accounts.charlie, 200)] {
+                set_sender(bidder);
+                test::set_value_transferred::<Environment>(bid);
+                assert!(marketplace.make_bid(contract_address(),Id::U64(1)).is_ok());
+            }
+
+            // The contract account has no balance off-chain until one is set, so the transfer is refused
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.withdraw_funds(), Err(MarketplaceError::WithdrawalFailed));
+        }
+
+        #[ink::test]
+        fn make_offer_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            let royalty: u16 = 150;
+
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
+            assert_eq!(marketplace.make_offer(contract_address(),Id::U64(3),100), Err(MarketplaceError::TokenDoesNotExist));
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.make_offer(contract_address(),Id::U64(3),100), Err(MarketplaceError::IneligibleOfferAmount));
+            test::set_value_transferred::<Environment>(500);
+            assert!(marketplace.make_offer(contract_address(),Id::U64(3),100).is_ok());
+            assert_eq!(marketplace.make_offer(contract_address(),Id::U64(3),100), Err(MarketplaceError::OfferAlreadyExists));
+
+            let offer = Offer { amount: 500, expiry: 100 };
+            assert_eq!(marketplace.get_offer(contract_address(),Id::U64(3),accounts.bob), Some(offer.clone()));
+            assert_eq!(marketplace.get_offers(contract_address(),Id::U64(3),0,10), vec![(accounts.bob, offer)]);
+            assert_eq!(marketplace.get_offers(contract_address(),Id::U64(3),1,10), vec![]);
+        }
+
+        #[ink::test]
+        fn full_offer_list_evicts_lowest() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
+
+            for n in 0..50u8 {
+                set_sender(AccountId::from([0x10 + n; 32]));
+                test::set_value_transferred::<Environment>(10 + u128::from(n));
+                assert!(marketplace.make_offer(contract_address(),Id::U64(3),5).is_ok());
+            }
+
+            // A full list only takes an offer that beats its lowest, which is credited back
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(10);
+            assert_eq!(marketplace.make_offer(contract_address(),Id::U64(3),100), Err(MarketplaceError::TooManyOffers));
+            test::set_value_transferred::<Environment>(20);
+            assert!(marketplace.make_offer(contract_address(),Id::U64(3),100).is_ok());
+            assert_eq!(marketplace.get_offer(contract_address(),Id::U64(3),AccountId::from([0x10; 32])), None);
+            assert_eq!(marketplace.get_pending_withdrawal(AccountId::from([0x10; 32])), 10);
+            assert_eq!(marketplace.get_offers(contract_address(),Id::U64(3),0,100).len(), 50);
+
+            // Expired offers are dropped before the limit is checked
+            test::advance_block::<Environment>();
+            set_sender(accounts.charlie);
+            test::set_value_transferred::<Environment>(1);
+            assert!(marketplace.make_offer(contract_address(),Id::U64(3),100).is_ok());
+            assert_eq!(marketplace.get_offers(contract_address(),Id::U64(3),0,100).len(), 2);
+            assert_eq!(marketplace.get_pending_withdrawal(AccountId::from([0x11; 32])), 11);
+        }
+
+        #[ink::test]
+        fn make_collection_offer_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            let royalty: u16 = 150;
+
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(250);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),100,3,100), Err(MarketplaceError::IneligibleOfferAmount));
+            test::set_value_transferred::<Environment>(300);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),100,3,100), Ok(1));
+
+            assert_eq!(marketplace.get_collection_offers(contract_address()), vec![CollectionOffer {
+                id: 1,
+                offerer: accounts.bob,
+                price: 100,
+                quantity: 3,
+                expiry: 100,
+            }]);
+        }
+
+        #[ink::test]
+        fn fill_collection_offer_after_cancel_fails() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            test::set_account_balance::<Environment>(test::callee::<Environment>(), 10000);
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1000);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),1000,1,100), Ok(1));
+            set_sender(accounts.charlie);
+            test::set_value_transferred::<Environment>(10);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),10,1,100), Ok(2));
+
+            // Bob cancels before alice's fill lands, so the fill fails instead of taking charlie's offer
+            set_sender(accounts.bob);
+            assert!(marketplace.cancel_collection_offer(contract_address(),1).is_ok());
+            set_sender(accounts.alice);
+            assert_eq!(marketplace.fill_collection_offer(contract_address(),Id::U64(1),1), Err(MarketplaceError::OfferNotFound));
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 0);
+            assert_eq!(marketplace.get_collection_offers(contract_address()).len(), 1);
+
+            assert!(marketplace.fill_collection_offer(contract_address(),Id::U64(1),2).is_ok());
+            assert_eq!(marketplace.get_collection_offers(contract_address()), vec![]);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 10);
+        }
+
+        #[ink::test]
+        fn full_collection_offer_book_evicts_lowest() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1);
+            for _ in 0..100 {
+                assert!(marketplace.make_collection_offer(contract_address(),1,1,5).is_ok());
+            }
+
+            // A full book only takes an offer that beats its lowest, which is credited back
+            set_sender(accounts.charlie);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),1,1,100), Err(MarketplaceError::TooManyOffers));
+            test::set_value_transferred::<Environment>(2);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),2,1,100), Ok(101));
+            assert_eq!(marketplace.get_collection_offers(contract_address()).len(), 100);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 1);
+
+            // Once bob's offers expire they are dropped to make room
+            test::advance_block::<Environment>();
+            set_sender(accounts.django);
+            test::set_value_transferred::<Environment>(1);
+            assert_eq!(marketplace.make_collection_offer(contract_address(),1,1,100), Ok(102));
+            assert_eq!(marketplace.get_collection_offers(contract_address()).len(), 2);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 100);
+        }
+
+        #[ink::test]
+        fn dutch_price_decays() {
+            let marketplace = init_contract();
+            let linear = DutchAuction {
+                start_price: 1000,
+                end_price: 200,
+                start_time: 100,
+                duration: 800,
+                step: 0,
+            };
+            assert_eq!(marketplace.calculate_dutch_price(&linear, 50), 1000);
+            assert_eq!(marketplace.calculate_dutch_price(&linear, 500), 600);
+            assert_eq!(marketplace.calculate_dutch_price(&linear, 5000), 200);
+
+            let stepped = DutchAuction { step: 300, ..linear };
+            assert_eq!(marketplace.calculate_dutch_price(&stepped, 399), 1000);
+            assert_eq!(marketplace.calculate_dutch_price(&stepped, 500), 700);
+            assert_eq!(marketplace.calculate_dutch_price(&stepped, 900), 200);
+        }
+
+        #[ink::test]
+        fn sales_breakdown_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            assert_eq!(
+                marketplace.get_sales_breakdown(contract_address(), Id::U8(1u8), 10000),
+                Err(MarketplaceError::CollectionNotRegisteredToMarketplace)
+            );
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,150).is_ok());
+            assert_eq!(
+                marketplace.get_sales_breakdown(contract_address(), Id::U8(1u8), 10000),
+                Ok((9750, 150, 100, accounts.alice))
+            );
+            assert_eq!(
+                marketplace.get_sales_breakdown(contract_address(), Id::U8(1u8), u128::MAX),
+                Err(MarketplaceError::ArithmeticOverflow)
+            );
+        }
+
+        #[ink::test]
+        fn create_collection_requires_contract_hash() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            assert_eq!(
+                marketplace.create_collection(name,symbol,hash,150),
+                Err(MarketplaceError::ContractHashNotSet)
+            );
+            assert_eq!(marketplace.get_creator_collections(accounts.alice), vec![]);
+            assert_eq!(marketplace.get_collection_count(), 0);
+        }
+
+        #[ink::test]
+        fn royalty_split_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            assert_eq!(
+                marketplace.set_royalty_split(contract_address(), vec![(accounts.bob, 10000)]),
+                Err(MarketplaceError::CollectionNotRegisteredToMarketplace)
+            );
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,1000).is_ok());
+            assert_eq!(
+                marketplace.set_royalty_split(contract_address(), vec![(accounts.bob, 5000), (accounts.charlie, 4000)]),
+                Err(MarketplaceError::InvalidRoyaltySplit)
+            );
+            assert_eq!(
+                marketplace.set_royalty_split(contract_address(), vec![(accounts.bob, 1000); 11]),
+                Err(MarketplaceError::TooManyRoyaltyRecipients)
+            );
+            let split = vec![(accounts.bob, 3333), (accounts.charlie, 6667)];
+            assert!(marketplace.set_royalty_split(contract_address(), split.clone()).is_ok());
+            assert_eq!(marketplace.get_royalty_split(contract_address()), split);
+
+            let breakdown = marketplace.get_sales_breakdown(contract_address(), Id::U8(1u8), 10000).unwrap();
+            assert!(marketplace.distribute_sale_proceeds(contract_address(), Id::U8(1u8), accounts.eve, accounts.frank, 10000, breakdown, None).is_ok());
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 333);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.charlie), 666);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 1);
+
+            // Royalties the collection sends elsewhere are paid in full to that receiver
+            marketplace.pay_royalties(contract_address(), accounts.django, None, 1000);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.django), 1000);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.bob), 333);
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.set_royalty_split(contract_address(), vec![]), Err(MarketplaceError::NotAuthorized));
+        }
+
+        #[ink::test]
+        fn max_royalty_works() {
+            let mut marketplace = init_contract();
+            assert_eq!(marketplace.get_max_royalty(), 1000);
+            assert_eq!(marketplace.set_max_royalty(10001), Err(MarketplaceError::RoyaltyTooHigh));
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            assert_eq!(
+                marketplace.add_collection(contract_address(),name.clone(),symbol.clone(),hash.clone(),1500),
+                Err(MarketplaceError::RoyaltyTooHigh)
+            );
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,1000).is_ok());
+
+            assert!(marketplace.set_max_royalty(500).is_ok());
+            let (_, royalties, _, _) = marketplace.get_sales_breakdown(contract_address(), Id::U8(1u8), 10000).unwrap();
+            assert_eq!(royalties, 500);
+        }
+
+        #[ink::test]
+        fn dutch_price_unavailable_before_start() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(1)).is_ok());
+            assert!(marketplace.create_dutch_auction(contract_address(),Id::U64(1),1000,0,10,100,0).is_ok());
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.get_current_price(contract_address(),Id::U64(1)), None);
+            test::set_value_transferred::<Environment>(1000);
+            assert_eq!(marketplace.close_direct_sale(contract_address(),Id::U64(1)), Err(MarketplaceError::AuctionNotStarted));
+
+            test::advance_block::<Environment>();
+            test::advance_block::<Environment>();
+            assert_eq!(marketplace.get_current_price(contract_address(),Id::U64(1)), Some(980));
+        }
+
+        #[ink::test]
+        fn batch_create_direct_sale_reports_each_item() {
+            let mut marketplace = init_contract();
+
+            let items = vec![(contract_address(),Id::U64(1),100),(contract_address(),Id::U64(2),100)];
+            assert_eq!(marketplace.batch_create_direct_sale(items.clone(), false), Ok(vec![
+                Err(MarketplaceError::CollectionNotRegisteredToMarketplace),
+                Err(MarketplaceError::CollectionNotRegisteredToMarketplace),
+            ]));
+            assert_eq!(marketplace.batch_create_direct_sale(items, true), Err(MarketplaceError::CollectionNotRegisteredToMarketplace));
+
+            let too_many = (0..101).map(|id| (contract_address(),Id::U64(id),100)).collect();
+            assert_eq!(marketplace.batch_create_direct_sale(too_many, false), Err(MarketplaceError::BatchTooLarge));
+        }
+
+        #[ink::test]
+        fn create_bundle_validates_items() {
+            let mut marketplace = init_contract();
+
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            let royalty: u16 = 150;
+
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,royalty).is_ok());
+            assert!(marketplace.create_market_item(contract_address(),Id::U64(3)).is_ok());
+
+            assert_eq!(marketplace.create_bundle(vec![(contract_address(),Id::U64(3))],100), Err(MarketplaceError::IneligibleBundleSize));
+            assert_eq!(marketplace.create_bundle(vec![(contract_address(),Id::U64(3)),(contract_address(),Id::U64(3))],100),
+                Err(MarketplaceError::DuplicateBundleItem));
+            assert_eq!(marketplace.create_bundle(vec![(contract_address(),Id::U64(4)),(contract_address(),Id::U64(3))],100),
+                Err(MarketplaceError::TokenDoesNotExist));
+            assert_eq!(marketplace.get_bundle(1), None);
+        }
+
+        #[ink::test]
+        fn buy_bundle_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            let items = vec![(contract_address(),Id::U64(1)),(contract_address(),Id::U64(2))];
+            for (address, token_id) in items.iter() {
+                assert!(marketplace.create_market_item(*address,token_id.clone()).is_ok());
+            }
+            assert_eq!(marketplace.create_bundle(items.clone(),1000), Ok(1));
+            assert_eq!(marketplace.get_item_status(contract_address(),Id::U64(1)), Some(ItemStatus::Bundled));
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(999);
+            assert_eq!(marketplace.buy_bundle(1), Err(MarketplaceError::IneligibleBuyPrice));
+            test::set_value_transferred::<Environment>(1000);
+            assert!(marketplace.buy_bundle(1).is_ok());
+
+            for (address, token_id) in items {
+                assert_eq!(marketplace.get_item(address,token_id.clone()).map(|item| item.owner), Some(accounts.bob));
+                assert_eq!(marketplace.get_item_status(address,token_id), Some(ItemStatus::Unlisted));
+            }
+            assert_eq!(marketplace.get_bundle(1), None);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 990);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 10);
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![]);
+        }
+
+        #[ink::test]
+        fn buy_bundle_fails_when_member_token_moved() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            let items = vec![(contract_address(),Id::U64(1)),(contract_address(),Id::U64(2))];
+            for (address, token_id) in items.iter() {
+                assert!(marketplace.create_market_item(*address,token_id.clone()).is_ok());
+            }
+            assert_eq!(marketplace.create_bundle(items,1000), Ok(1));
+
+            // The second token leaves escrow behind the marketplace's back
+            move_token(contract_address(), Id::U64(2), accounts.django);
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(1000);
+            assert_eq!(marketplace.buy_bundle(1), Err(MarketplaceError::TokenTransferFailed));
+        }
+
+        #[ink::test]
+        fn settle_bundle_auction_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            let items = vec![(contract_address(),Id::U64(1)),(contract_address(),Id::U64(2))];
+            for (address, token_id) in items.iter() {
+                assert!(marketplace.create_market_item(*address,token_id.clone()).is_ok());
+            }
+            assert_eq!(marketplace.create_bundle_auction(items.clone(),100,5), Ok(1));
+
+            set_sender(accounts.bob);
+            test::set_value_transferred::<Environment>(200);
+            assert_eq!(marketplace.buy_bundle(1), Err(MarketplaceError::TokenNotForDirectSale));
+            assert!(marketplace.bid_bundle(1).is_ok());
+
+            set_sender(accounts.alice);
+            assert_eq!(marketplace.cancel_bundle(1), Err(MarketplaceError::MinimumBidAlreadyMet));
+
+            set_sender(accounts.charlie);
+            assert_eq!(marketplace.settle_bundle(1), Err(MarketplaceError::AuctionOngoing));
+            test::advance_block::<Environment>();
+            assert!(marketplace.settle_bundle(1).is_ok());
+
+            for (address, token_id) in items {
+                assert_eq!(marketplace.get_item(address,token_id.clone()).map(|item| item.owner), Some(accounts.bob));
+                assert_eq!(marketplace.get_item_status(address,token_id), Some(ItemStatus::Unlisted));
+            }
+            assert_eq!(marketplace.get_bundle(1), None);
+            assert_eq!(marketplace.get_pending_withdrawal(accounts.alice), 198);
+            assert_eq!(marketplace.get_pending_withdrawal(fee_recipient()), 2);
+        }
+
+        #[ink::test]
+        fn cancel_bundle_works() {
+            let mut marketplace = init_contract();
+            let accounts = default_accounts();
+            set_sender(accounts.alice);
+            add_test_collection(&mut marketplace);
+            let items = vec![(contract_address(),Id::U64(1)),(contract_address(),Id::U64(2))];
+            for (address, token_id) in items.iter() {
+                assert!(marketplace.create_market_item(*address,token_id.clone()).is_ok());
+            }
+            assert_eq!(marketplace.create_bundle(items.clone(),1000), Ok(1));
+
+            set_sender(accounts.bob);
+            assert_eq!(marketplace.cancel_bundle(1), Err(MarketplaceError::NotTheOwner));
+
+            set_sender(accounts.alice);
+            assert!(marketplace.cancel_bundle(1).is_ok());
+            assert_eq!(marketplace.cancel_bundle(1), Err(MarketplaceError::BundleNotFound));
+            for (address, token_id) in items {
+                assert_eq!(marketplace.get_item(address,token_id.clone()).map(|item| item.owner), Some(accounts.alice));
+                assert_eq!(marketplace.get_item_status(address,token_id), Some(ItemStatus::Unlisted));
+            }
+            assert_eq!(marketplace.get_escrowed_items(0,10), vec![]);
+        }
+
+        fn revoke_approval(address: AccountId, token_id: Id) {
+            REVOKED_APPROVALS.with(|revoked| revoked.borrow_mut().push((address, token_id)));
+        }
+
+        fn fund_currency(currency: AccountId, account: AccountId, amount: Balance) {
+            CURRENCY_BALANCES.with(|balances| balances.borrow_mut().push(((currency, account), amount)));
+        }
+
+        fn move_token(address: AccountId, token_id: Id, to: AccountId) {
+            TOKEN_OWNERS.with(|owners| {
+                let mut owners = owners.borrow_mut();
+                owners.retain(|(key, _)| *key != (address, token_id.clone()));
+                owners.push(((address, token_id), to));
+            });
+        }
+
+        fn add_test_collection(marketplace: &mut MarketplaceContract) {
+            let name = String::from("Test Collection");
+            let symbol = String::from("TST");
+            let hash = String::from("https://ipfs.io/aaa");
+            assert!(marketplace.add_collection(contract_address(),name,symbol,hash,150).is_ok());
+        }
+
         fn init_contract() -> MarketplaceContract {
             MarketplaceContract::new(fee_recipient())
         }
@@ -149,8 +1768,9 @@ pub mod marketplace {
             ink::env::test::set_caller::<ink::env::DefaultEnvironment>(sender);
         }
 
+        // Kept apart from the default accounts, so payouts to the fee recipient and to alice can be told apart
         fn fee_recipient() -> AccountId {
-            AccountId::from([0x1; 32])
+            AccountId::from([0x7; 32])
         }
 
         fn contract_address() -> AccountId {
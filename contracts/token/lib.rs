@@ -1,5 +1,7 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 #![feature(min_specialization)]
+
+pub mod royalty;
         
 #[openbrush::contract]
 pub mod token {
@@ -16,6 +18,11 @@ pub mod token {
 		impls::custom_mint::*,
 	};
 
+	use crate::royalty::{
+		self,
+		PSP34Royalty,
+	};
+
     #[ink(storage)]
     #[derive(Default, Storage)]
     pub struct Token {
@@ -27,6 +34,8 @@ pub mod token {
 		metadata: metadata::Data,
 		#[storage_field]
 		custom_mint: types::Data,
+		#[storage_field]
+		royalty: royalty::Data,
     }
     
     // Section contains default implementation without any modifications
@@ -35,6 +44,7 @@ pub mod token {
 	impl PSP34Enumerable for Token {}
 	impl PSP34Metadata for Token {}
 	impl CustomMint for Token {}
+	impl PSP34Royalty for Token {}
      
     impl Token {
         #[ink(constructor)]
@@ -42,6 +52,7 @@ pub mod token {
 			name: String,
 			symbol: String,
 			base_uri: String,
+			creator: AccountId,
 		) -> Self {
 				let mut instance = Self::default();
 				instance._init_with_owner(instance.env().caller());
@@ -50,7 +61,9 @@ pub mod token {
 				instance._set_attribute(collection_id.clone(), String::from("symbol"), symbol);
 				instance._set_attribute(collection_id, String::from("baseUri"), base_uri);
 				instance.custom_mint.last_token_id = 0;
-				instance.custom_mint.creator = instance.env().caller();
+				// Collections deployed by the marketplace name their creator rather than the marketplace
+				instance.custom_mint.creator = creator;
+				instance.royalty.receiver = creator;
 				instance
 		}
 
@@ -65,9 +78,13 @@ pub mod token {
 
 		#[ink::test]
 		fn new_works() {
-			let contract = Token::new(String::from("Test"),String::from("TST"),String::from("https://ipfs/1"));
+			let accounts = default_accounts();
+			let contract = Token::new(String::from("Test"),String::from("TST"),String::from("https://ipfs/1"),accounts.bob);
 			let collection_id = contract.collection_id();
 
+			assert_eq!(contract.owner(), accounts.alice);
+			assert_eq!(contract.custom_mint.creator, accounts.bob);
+
 			assert_eq!(contract.get_attribute(collection_id.clone(), String::from("name")),
 			Some(String::from("Test")));
 
@@ -80,7 +97,7 @@ pub mod token {
 
 		#[ink::test]
 		fn mint_works() {
-			let mut contract = Token::new(String::from("Test"),String::from("TST"),String::from("https://ipfs/1"));
+			let mut contract = Token::new(String::from("Test"),String::from("TST"),String::from("https://ipfs/1"),default_accounts().alice);
 
 			let accounts = default_accounts();
 
@@ -109,6 +126,27 @@ pub mod token {
 			
 		}
 
+		#[ink::test]
+		fn royalty_works() {
+			let mut contract = Token::new(String::from("Test"),String::from("TST"),String::from("https://ipfs/1"),default_accounts().alice);
+
+			let accounts = default_accounts();
+
+			assert_eq!(contract.royalty_info(Id::U64(1), 10000), (accounts.alice, 0));
+			assert_eq!(contract.set_default_royalty(accounts.bob, 10001), Err(royalty::RoyaltyError::RoyaltyTooHigh));
+			assert!(contract.set_default_royalty(accounts.bob, 500).is_ok());
+			assert!(contract.set_token_royalty(Id::U64(2), accounts.eve, 250).is_ok());
+
+			assert_eq!(contract.royalty_info(Id::U64(1), 10000), (accounts.bob, 500));
+			assert_eq!(contract.royalty_info(Id::U64(2), 10000), (accounts.eve, 250));
+
+			assert!(contract.reset_token_royalty(Id::U64(2)).is_ok());
+			assert_eq!(contract.royalty_info(Id::U64(2), 10000), (accounts.bob, 500));
+
+			set_sender(accounts.bob);
+			assert!(contract.set_default_royalty(accounts.bob, 100).is_err());
+		}
+
 		fn default_accounts() -> test::DefaultAccounts<ink::env::DefaultEnvironment> {
             test::default_accounts::<Environment>()
         }
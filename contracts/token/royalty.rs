@@ -0,0 +1,103 @@
+use openbrush::{
+    contracts::{
+        ownable::*,
+        psp34::Id,
+    },
+    modifiers,
+    storage::Mapping,
+    traits::{
+        AccountId,
+        Balance,
+        Storage,
+    },
+};
+
+pub const STORAGE_KEY: u32 = openbrush::storage_unique_key!(Data);
+
+#[derive(Default, Debug)]
+#[openbrush::upgradeable_storage(STORAGE_KEY)]
+pub struct Data {
+    pub receiver: AccountId,
+    pub royalty: u16,
+    pub token_royalties: Mapping<Id, (AccountId, u16)>,
+}
+
+#[derive(Debug, PartialEq, Eq, scale::Encode, scale::Decode)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum RoyaltyError {
+    /// Caller is not the collection owner.
+    OwnableError(OwnableError),
+    // Royalty Too High
+    RoyaltyTooHigh,
+}
+
+impl From<OwnableError> for RoyaltyError {
+    fn from(error: OwnableError) -> Self {
+        RoyaltyError::OwnableError(error)
+    }
+}
+
+#[openbrush::wrapper]
+pub type PSP34RoyaltyRef = dyn PSP34Royalty;
+
+/// Royalty interface the marketplace queries when a token of the collection sells.
+#[openbrush::trait_definition]
+pub trait PSP34Royalty {
+    /// Returns the account to pay and the royalty owed when `token_id` sells for `sale_price`.
+    #[ink(message)]
+    fn royalty_info(&self, token_id: Id, sale_price: Balance) -> (AccountId, Balance);
+
+    /// Royalty receiver and basis points used by tokens without an override.
+    #[ink(message)]
+    fn set_default_royalty(&mut self, receiver: AccountId, royalty: u16) -> Result<(), RoyaltyError>;
+
+    #[ink(message)]
+    fn get_default_royalty(&self) -> (AccountId, u16);
+
+    #[ink(message)]
+    fn set_token_royalty(&mut self, token_id: Id, receiver: AccountId, royalty: u16) -> Result<(), RoyaltyError>;
+
+    /// Drops the override so the token pays the default royalty again.
+    #[ink(message)]
+    fn reset_token_royalty(&mut self, token_id: Id) -> Result<(), RoyaltyError>;
+}
+
+impl<T> PSP34Royalty for T
+where
+    T: Storage<Data> + Storage<ownable::Data>,
+{
+    default fn royalty_info(&self, token_id: Id, sale_price: Balance) -> (AccountId, Balance) {
+        let (receiver, royalty) = self.data::<Data>().token_royalties.get(&token_id)
+            .unwrap_or((self.data::<Data>().receiver, self.data::<Data>().royalty));
+        (receiver, sale_price.saturating_mul(u128::from(royalty)) / 10000)
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_default_royalty(&mut self, receiver: AccountId, royalty: u16) -> Result<(), RoyaltyError> {
+        if royalty > 10000 {
+            return Err(RoyaltyError::RoyaltyTooHigh)
+        }
+        self.data::<Data>().receiver = receiver;
+        self.data::<Data>().royalty = royalty;
+        Ok(())
+    }
+
+    default fn get_default_royalty(&self) -> (AccountId, u16) {
+        (self.data::<Data>().receiver, self.data::<Data>().royalty)
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_token_royalty(&mut self, token_id: Id, receiver: AccountId, royalty: u16) -> Result<(), RoyaltyError> {
+        if royalty > 10000 {
+            return Err(RoyaltyError::RoyaltyTooHigh)
+        }
+        self.data::<Data>().token_royalties.insert(&token_id, &(receiver, royalty));
+        Ok(())
+    }
+
+    #[modifiers(only_owner)]
+    default fn reset_token_royalty(&mut self, token_id: Id) -> Result<(), RoyaltyError> {
+        self.data::<Data>().token_royalties.remove(&token_id);
+        Ok(())
+    }
+}
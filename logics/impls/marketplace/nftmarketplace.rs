@@ -27,9 +27,28 @@ use crate::{
         AuctionItem,
         MarketplaceError,
         Collection,
+        Bundle,
+        DutchAuction,
+        SealedAuction,
+        SealedBid,
+        Offer,
+        CollectionOffer,
+        ItemIndex,
+        ItemStatus,
+        ListingHealth,
+        MAX_OFFERS_PER_TOKEN,
+        MAX_COLLECTION_OFFERS,
+        MAX_SEALED_BIDS,
+        MAX_BATCH_SIZE,
+        MAX_BUNDLE_SIZE,
+        MAX_PAGE_SIZE,
+        MAX_ROYALTY_RECIPIENTS,
+        MAX_COLLECTIONS_PER_CREATOR,
+        ROYALTY_QUERY_GAS,
     },
     traits::marketplace::NFTMarketplace,
 };
+use ink::env::hash::Blake2x256;
 use ink::primitives::Hash;
 use ink::prelude::vec::Vec;
 //use ink::Blake2x256;
@@ -37,6 +56,7 @@ use ink::prelude::vec::Vec;
 use openbrush::{
     contracts::{
         ownable::*,
+        psp22::PSP22Ref,
         psp34::*,
         reentrancy_guard::*,
     },
@@ -51,50 +71,262 @@ use openbrush::{
     },
 };
 use ink::ToAccountId;
-use token::token::TokenRef;
+use token::{
+    royalty::PSP34RoyaltyRef as TokenRoyaltyRef,
+    token::TokenRef,
+};
 
 pub trait Internal {
-    fn calculate_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Balance;
+    fn calculate_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Result<Balance,MarketplaceError>;
+
+    fn get_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Result<Balance,MarketplaceError>;
+
+    fn calculate_dutch_price(&self, dutch: &DutchAuction, timestamp: Timestamp) -> Balance;
+
+    fn get_ended_auction(&self, address: AccountId, token_id: Id) -> Result<AuctionItem,MarketplaceError>;
+
+    /// Ends an auction without a sale. Bids are refunded and the token goes back to the seller.
+    fn return_unsold_auction(&mut self, address: AccountId, token_id: Id, item: AuctionItem, seller: AccountId) -> Result<(),MarketplaceError>;
+
+    fn reserve_grace_over(&self, item: &AuctionItem) -> bool;
+
+    fn get_direct_sale_price(&self, item: &AuctionItem) -> Result<Balance,MarketplaceError>;
+
+    fn listing_expired(&self, item: &AuctionItem) -> bool;
+
+    fn get_open_auction(&self, address: AccountId, token_id: Id) -> Result<AuctionItem,MarketplaceError>;
+
+    fn place_bid(&mut self, address: AccountId, token_id: Id, item: AuctionItem, bidder: AccountId, value: Balance) -> Result<(),MarketplaceError>;
+
+    fn check_currency(&self, currency: Option<AccountId>) -> Result<(),MarketplaceError>;
+
+    fn receive_payment(&mut self, currency: AccountId, from: AccountId, amount: Balance) -> Result<(),MarketplaceError>;
+
+    fn list_bundle(&mut self, items: Vec<(AccountId, Id)>, price: Balance, direct: bool, min_bid: Balance, bid_end_time: Timestamp) -> Result<u64,MarketplaceError>;
+
+    fn complete_bundle_sale(&mut self, bundle_id: u64, bundle: Bundle, buyer: AccountId, price: Balance) -> Result<(),MarketplaceError>;
+
+    fn buy_item(&mut self, address: AccountId, token_id: Id, buyer: AccountId, available: Balance) -> Result<Balance,MarketplaceError>;
+
+    fn get_clearing_price(&self, item: &AuctionItem) -> Balance;
 
-    fn get_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Balance;
+    fn release_sealed_deposits(&mut self, address: AccountId, token_id: Id, winner: Option<AccountId>, price: Balance, seller: AccountId);
 
-    fn update_highest_bid(&self, address: AccountId, token_id: Id, new_bidder: AccountId, new_bid_amount: Balance) -> Result<(Balance,Option<AccountId>),MarketplaceError>;
+    fn update_highest_bid(&mut self, address: AccountId, token_id: Id, new_bidder: AccountId, new_bid_amount: Balance) -> Result<(Balance,Option<AccountId>),MarketplaceError>;
 
     fn check_token_exists(&self, address: AccountId, token_id: Id) -> bool;
 
     fn check_collection_exists(&self, address: AccountId) -> bool;
 
+    fn check_collection_active(&self, address: AccountId) -> Result<(),MarketplaceError>;
+
+    /// Collection the caller may manage, as its creator or as the marketplace owner.
+    fn get_managed_collection(&self, address: AccountId) -> Result<Collection,MarketplaceError>;
+
     fn set_auction_end(&mut self, address: AccountId, token_id: Id) -> Result<(),MarketplaceError>;
 
-    fn finalize_sale(&self, address: AccountId, token_id: Id, sales_price: Balance) -> Result<(),MarketplaceError>;
+    fn check_transition(&self, item: &AuctionItem, status: ItemStatus) -> Result<(),MarketplaceError>;
+
+    /// The only place where an item's status changes. Starting a listing assigns it a new listing id.
+    fn transition(&mut self, address: AccountId, token_id: Id, item: &mut AuctionItem, status: ItemStatus) -> Result<(),MarketplaceError>;
+
+    /// Stored status, with auctions past their end time reported as awaiting settlement.
+    fn get_status(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> ItemStatus;
+
+    fn is_settleable(&self, item: &AuctionItem) -> bool;
+
+    /// Pays the keeper their share of `market_fees`, the fee already credited for the sale.
+    fn pay_keeper_reward(&mut self, address: AccountId, token_id: Id, keeper: AccountId, market_fees: Balance, currency: Option<AccountId>)
+    -> Result<Balance,MarketplaceError>;
+
+    fn debit_currency(&mut self, account: AccountId, currency: Option<AccountId>, amount: Balance);
+
+    /// Credits royalties following the collection's split, with rounding dust going to `receiver`.
+    /// The split only applies when the royalties go to the collection creator.
+    fn pay_royalties(&mut self, address: AccountId, receiver: AccountId, currency: Option<AccountId>, royalties: Balance);
+
+    fn get_token_owner(&self, address: AccountId, token_id: Id) -> Option<AccountId>;
+
+    fn transfer_token(&mut self, address: AccountId, to: AccountId, token_id: Id) -> Result<(),PSP34Error>;
+
+    /// Royalty receiver and amount reported by the collection, if it implements `PSP34Royalty`.
+    /// Only `get_sales_breakdown` calls it, so a sale queries the collection once.
+    fn get_royalty_info(&self, address: AccountId, token_id: Id, sales_price: Balance) -> Option<(AccountId, Balance)>;
+
+    /// Whether `owner` approved the marketplace for the token, or for the whole collection.
+    fn is_approved(&self, address: AccountId, token_id: Id, owner: AccountId) -> bool;
+
+    /// Checks `account` currently owns the token and records it as the item owner.
+    fn check_token_owner(&self, address: AccountId, token_id: Id, item: &mut AuctionItem, account: AccountId) -> Result<(),MarketplaceError>;
+
+    fn listing_valid(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> bool;
+
+    fn check_listing_valid(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> Result<(),MarketplaceError>;
+
+    /// Cancels a listing whose token moved away, returning every bid to its bidder.
+    fn invalidate_listing(&mut self, address: AccountId, token_id: Id, item: AuctionItem) -> Result<(),MarketplaceError>;
+
+    fn take_custody(&mut self, address: AccountId, token_id: Id) -> Result<(),MarketplaceError>;
+
+    fn release_custody(&mut self, address: AccountId, token_id: Id);
+
+    fn index_contains(&self, index: ItemIndex, address: AccountId, token_id: Id) -> bool;
+
+    fn index_insert(&mut self, index: ItemIndex, address: AccountId, token_id: Id);
+
+    fn index_remove(&mut self, index: ItemIndex, address: AccountId, token_id: Id);
+
+    fn index_page(&self, index: ItemIndex, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    /// Sells the listed item to `buyer` and returns the market fee taken.
+    fn finalize_sale(&mut self, address: AccountId, token_id: Id, buyer: AccountId, sales_price: Balance) -> Result<Balance,MarketplaceError>;
+
+    /// Everything `finalize_sale` can fail on short of the token transfer, checked before any
+    /// payment is taken or token moved. Returns the seller and the sales breakdown.
+    fn check_sale(&self, address: AccountId, token_id: Id, item: &AuctionItem, buyer: AccountId, sales_price: Balance)
+    -> Result<(AccountId, (Balance, Balance, Balance, AccountId)),MarketplaceError>;
+
+    /// Delivers the token of a checked sale and pays out its breakdown.
+    fn complete_sale(&mut self, address: AccountId, token_id: Id, seller: AccountId, buyer: AccountId, sales_price: Balance,
+        breakdown: (Balance, Balance, Balance, AccountId), currency: Option<AccountId>) -> Result<(),MarketplaceError>;
+
+    fn distribute_sale_proceeds(&mut self, address: AccountId, token_id: Id, seller: AccountId, buyer: AccountId, sales_price: Balance,
+        breakdown: (Balance, Balance, Balance, AccountId), currency: Option<AccountId>) -> Result<(),MarketplaceError>;
 
     fn get_sales_breakdown(&self, address: AccountId, token_id: Id, sales_price: Balance) 
-    -> (Balance, Balance, Balance, AccountId);
+    -> Result<(Balance, Balance, Balance, AccountId),MarketplaceError>;
+
+    fn credit_account(&mut self, account: AccountId, amount: Balance);
+
+    fn credit_currency(&mut self, account: AccountId, currency: Option<AccountId>, amount: Balance);
+
+    fn remove_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<Offer,MarketplaceError>;
+
+    /// Removes an offer nobody can accept any more and credits its escrow back to the offerer.
+    fn refund_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<(),MarketplaceError>;
+
+    /// Credits the remaining escrow of a collection offer that was taken off the book back to its offerer.
+    fn refund_collection_offer(&mut self, address: AccountId, offer: CollectionOffer) -> Result<(),MarketplaceError>;
+}
+
+/// Event hooks called by the generic marketplace logic. The contract overrides
+/// them with its own `#[ink(event)]` types so the events end up in its metadata.
+pub trait MarketplaceEvents {
+    fn emit_collection_added_event(&self, collection: AccountId, creator: AccountId, royalty: u16);
+
+    fn emit_item_registered_event(&self, collection: AccountId, token_id: Id, owner: AccountId);
+
+    fn emit_listed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, price: Balance);
+
+    fn emit_auction_created_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, buy_now: Option<Balance>, min_bid: Balance, end_time: Timestamp);
+
+    fn emit_dutch_auction_created_event(&self, collection: AccountId, token_id: Id, seller: AccountId, start_price: Balance, end_price: Balance, start_time: Timestamp, duration: Timestamp);
+
+    fn emit_sealed_auction_created_event(&self, collection: AccountId, token_id: Id, seller: AccountId, min_bid: Balance, commit_end_time: Timestamp, reveal_end_time: Timestamp, vickrey: bool);
+
+    fn emit_bid_committed_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, deposit: Balance);
+
+    fn emit_bid_revealed_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, amount: Balance);
+
+    fn emit_bid_placed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, bidder: AccountId, amount: Balance, next_min_bid: Balance, end_time: Timestamp);
+
+    fn emit_auction_extension_set_event(&self, collection: AccountId, token_id: Id, extension_window: Timestamp, extension_length: Timestamp, max_end_time: Option<Timestamp>);
+
+    fn emit_outbid_event(&self, collection: AccountId, token_id: Id, bidder: AccountId, refund: Balance);
+
+    /// `listing_id` is 0 for tokens that were not listed when sold, such as through an offer.
+    fn emit_sold_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId, buyer: AccountId, price: Balance,
+        seller_share: Balance, royalties: Balance, market_fee: Balance, creator: AccountId, currency: Option<AccountId>);
+
+    fn emit_auction_settled_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: Option<AccountId>, winner: AccountId, price: Balance);
+
+    fn emit_listing_withdrawn_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: AccountId);
+
+    fn emit_listing_expired_event(&self, collection: AccountId, token_id: Id, seller: AccountId);
+
+    fn emit_status_changed_event(&self, collection: AccountId, token_id: Id, listing_id: u64, status: ItemStatus);
+
+    fn emit_collection_deactivated_event(&self, collection: AccountId, caller: AccountId);
+
+    fn emit_royalty_split_set_event(&self, collection: AccountId, caller: AccountId, split: Vec<(AccountId, u16)>);
+
+    fn emit_collection_removed_event(&self, collection: AccountId, caller: AccountId);
+
+    fn emit_item_removed_event(&self, collection: AccountId, token_id: Id, owner: AccountId);
+
+    fn emit_owner_synced_event(&self, collection: AccountId, token_id: Id, owner: AccountId);
+
+    fn emit_listing_invalidated_event(&self, collection: AccountId, token_id: Id, listing_id: u64, seller: Option<AccountId>);
+
+    fn emit_keeper_rewarded_event(&self, collection: AccountId, token_id: Id, keeper: AccountId, amount: Balance, currency: Option<AccountId>);
+
+    fn emit_direct_sale_updated_event(&self, collection: AccountId, token_id: Id, seller: AccountId, price: Balance);
+
+    fn emit_auction_updated_event(&self, collection: AccountId, token_id: Id, seller: AccountId, buy_now: Option<Balance>,
+        min_bid: Balance, end_time: Timestamp);
+
+    fn emit_funds_withdrawn_event(&self, account: AccountId, amount: Balance);
+
+    fn emit_token_funds_withdrawn_event(&self, account: AccountId, currency: AccountId, amount: Balance);
+
+    fn emit_bundle_created_event(&self, bundle_id: u64, seller: AccountId, items: Vec<(AccountId, Id)>, price: Balance, min_bid: Balance, end_time: Timestamp);
+
+    fn emit_bundle_bid_placed_event(&self, bundle_id: u64, bidder: AccountId, amount: Balance);
+
+    fn emit_bundle_sold_event(&self, bundle_id: u64, seller: AccountId, buyer: AccountId, price: Balance);
+
+    fn emit_bundle_cancelled_event(&self, bundle_id: u64, seller: AccountId);
+
+    fn emit_offer_made_event(&self, collection: AccountId, token_id: Id, offerer: AccountId, amount: Balance, expiry: Timestamp);
+
+    fn emit_offer_cancelled_event(&self, collection: AccountId, token_id: Id, offerer: AccountId, amount: Balance);
+
+    fn emit_offer_accepted_event(&self, collection: AccountId, token_id: Id, seller: AccountId, offerer: AccountId, amount: Balance);
+
+    fn emit_collection_offer_made_event(&self, collection: AccountId, offer_id: u64, offerer: AccountId, price: Balance, quantity: u32, expiry: Timestamp);
+
+    fn emit_collection_offer_cancelled_event(&self, collection: AccountId, offer_id: u64, offerer: AccountId, refund: Balance);
+
+    fn emit_collection_offer_filled_event(&self, collection: AccountId, offer_id: u64, token_id: Id, seller: AccountId, offerer: AccountId, price: Balance, remaining: u32);
 }
 
 impl<T> NFTMarketplace for T
 where
     T: Storage<Data> + Storage<ownable::Data> + Storage<reentrancy_guard::Data>,
 {
-    /*
     default fn create_collection(&mut self, name: String, symbol: String, collection_hash: String, royalty: u16 ) -> Result<AccountId, MarketplaceError> {
         let contract_hash = self.data::<Data>().contract_hash;
         if contract_hash == Hash::default() {
             return Err(MarketplaceError::ContractHashNotSet)
         }
+        if royalty > self.data::<Data>().max_royalty {
+            return Err(MarketplaceError::RoyaltyTooHigh)
+        }
 
-        let collection_count = self.data::<Data>().collection_count.saturating_add(1);
         let caller = Self::env().caller();
-        let salt = Self::env().hash_encoded::<Blake2x256, _>(&(caller.clone(), collection_count));
+        let mut created = self.data::<Data>().creator_collections.get(&caller).unwrap_or_default();
+        if created.len() >= MAX_COLLECTIONS_PER_CREATOR {
+            return Err(MarketplaceError::TooManyCollections)
+        }
+        // Removed collections drop out of `creator_collections`, so the salt uses a nonce that only grows
+        let nonce = self.data::<Data>().collection_nonce.saturating_add(1);
+        self.data::<Data>().collection_nonce = nonce;
+        let salt = Self::env().hash_encoded::<Blake2x256, _>(&(caller.clone(), nonce));
 
-        let nft = TokenRef::new(name.clone(),symbol.clone(),collection_hash.clone())
+        let nft = TokenRef::new(name.clone(),symbol.clone(),collection_hash.clone(),caller)
         .endowment(0)
         .code_hash(contract_hash)
-        .salt_bytes(&salt[..4])
+        .salt_bytes(&salt[..])
         .instantiate()
         .map_err(|_| MarketplaceError::TokenInstantiationFailed)?;
 
+        // The marketplace owns the new collection until its royalty is set up for the caller
         let contract_address = nft.to_account_id();
+        TokenRoyaltyRef::set_default_royalty(&contract_address, caller, royalty)
+            .map_err(|_| MarketplaceError::TokenInstantiationFailed)?;
+        OwnableRef::transfer_ownership(&contract_address, caller)
+            .map_err(|_| MarketplaceError::TokenInstantiationFailed)?;
+
         self.data::<Data>().collections.insert(
             &contract_address,
             &Collection {
@@ -103,14 +335,23 @@ where
                 ipfs: collection_hash,
                 creator: Some(caller.clone()),
                 royalty: royalty,
+                active: true,
             },
         );
+        created.push(contract_address);
+        self.data::<Data>().creator_collections.insert(&caller, &created);
 
+        let collection_count = self.data::<Data>().collection_count.saturating_add(1);
         self.data::<Data>().collection_count = collection_count;
+        self.emit_collection_added_event(contract_address, caller, royalty);
 
         Ok(contract_address)
     }
-*/
+
+    default fn get_creator_collections(&self, creator: AccountId) -> Vec<AccountId> {
+        self.data::<Data>().creator_collections.get(&creator).unwrap_or_default()
+    }
+
     default fn add_collection(&mut self, address: AccountId, name: String, symbol: String, collection_hash: String, royalty: u16 ) -> Result<(), MarketplaceError> {
         let caller = Self::env().caller();
 
@@ -119,6 +360,10 @@ where
             return Err(MarketplaceError::NotTheOwner)
         }
 
+        if royalty > self.data::<Data>().max_royalty {
+            return Err(MarketplaceError::RoyaltyTooHigh)
+        }
+
         if self.data::<Data>().collections.get(&address).is_some() {
             return Err(MarketplaceError::CollectionAlreadyExists)
         } else {
@@ -130,11 +375,13 @@ where
                     ipfs: collection_hash,
                     creator: Some(caller.clone()),
                     royalty: royalty,
+                    active: true,
                 },
             );
     
             let collection_count = self.data::<Data>().collection_count.saturating_add(1);
             self.data::<Data>().collection_count = collection_count;
+            self.emit_collection_added_event(address, caller, royalty);
         }
 
         Ok(())
@@ -144,8 +391,91 @@ where
         self.data::<Data>().collections.get(&address)
     }
 
+    default fn deactivate_collection(&mut self, address: AccountId) -> Result<(), MarketplaceError> {
+        let mut collection = self.get_managed_collection(address)?;
+        if !collection.active {
+            return Err(MarketplaceError::CollectionInactive)
+        }
+
+        collection.active = false;
+        self.data::<Data>().collections.insert(&address, &collection);
+        self.emit_collection_deactivated_event(address, Self::env().caller());
+
+        Ok(())
+    }
+
+    default fn remove_collection(&mut self, address: AccountId) -> Result<(), MarketplaceError> {
+        let collection = self.get_managed_collection(address)?;
+        if self.get_index_length(ItemIndex::Collection(address)) > 0 {
+            return Err(MarketplaceError::CollectionHasItems)
+        }
+
+        // Open collection offers hold escrowed funds, so their offerers are credited back
+        for offer in self.data::<Data>().collection_offers.get(&address).unwrap_or_default() {
+            self.refund_collection_offer(address, offer)?;
+        }
+        self.data::<Data>().collection_offers.remove(&address);
+        self.data::<Data>().royalty_splits.remove(&address);
+        if let Some(creator) = collection.creator {
+            let mut created = self.data::<Data>().creator_collections.get(&creator).unwrap_or_default();
+            if let Some(index) = created.iter().position(|collection| *collection == address) {
+                created.remove(index);
+                self.data::<Data>().creator_collections.insert(&creator, &created);
+            }
+        }
+        self.data::<Data>().collections.remove(&address);
+        let collection_count = self.data::<Data>().collection_count.saturating_sub(1);
+        self.data::<Data>().collection_count = collection_count;
+        self.emit_collection_removed_event(address, Self::env().caller());
+
+        Ok(())
+    }
+
+    default fn set_royalty_split(&mut self, address: AccountId, split: Vec<(AccountId, u16)>) -> Result<(), MarketplaceError> {
+        let collection = self.data::<Data>().collections.get(&address)
+            .ok_or(MarketplaceError::CollectionNotRegisteredToMarketplace)?;
+        let caller = Self::env().caller();
+        if collection.creator != Some(caller) {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        if split.len() > MAX_ROYALTY_RECIPIENTS {
+            return Err(MarketplaceError::TooManyRoyaltyRecipients)
+        }
+
+        if split.is_empty() {
+            self.data::<Data>().royalty_splits.remove(&address);
+        } else {
+            let total = split.iter().map(|(_, share)| u32::from(*share)).sum::<u32>();
+            if total != 10000 || split.iter().any(|(_, share)| *share == 0) {
+                return Err(MarketplaceError::InvalidRoyaltySplit)
+            }
+            self.data::<Data>().royalty_splits.insert(&address, &split);
+        }
+        self.emit_royalty_split_set_event(address, caller, split);
+
+        Ok(())
+    }
+
+    default fn get_royalty_split(&self, address: AccountId) -> Vec<(AccountId, u16)> {
+        self.data::<Data>().royalty_splits.get(&address).unwrap_or_default()
+    }
+
     default fn get_item(&self, address: AccountId,token_id: Id) -> Option<AuctionItem> {
+        let mut item = self.data::<Data>().items.get(&(address,token_id.clone()))?;
+        if item.reserve_hidden {
+            item.reserve_price = 0;
+        }
+        item.status = self.get_status(address, token_id, &item);
+        Some(item)
+    }
+
+    default fn get_item_status(&self, address: AccountId, token_id: Id) -> Option<ItemStatus> {
+        self.data::<Data>().items.get(&(address,token_id.clone())).map(|item| self.get_status(address, token_id, &item))
+    }
+
+    default fn is_reserve_met(&self, address: AccountId, token_id: Id) -> bool {
         self.data::<Data>().items.get(&(address,token_id))
+            .map_or(false, |item| item.highest_bidder.is_some() && item.highest_bid >= item.reserve_price)
     }
 
     default fn get_collection_count(&self) -> u64 {
@@ -165,291 +495,1572 @@ where
     }
 
     default fn create_market_item(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
-        if !self.check_collection_exists(address.clone()) {
-            return Err(MarketplaceError::CollectionNotRegisteredToMarketplace)
-        }
+        let caller = Self::env().caller();
+        self.check_collection_active(address)?;
 
         if self.check_token_exists(address,token_id.clone()) {
             return Err(MarketplaceError::TokenAlreadyExists)
         }
+        if self.get_token_owner(address, token_id.clone()).ok_or(MarketplaceError::TokenDoesNotExist)? != caller {
+            return Err(MarketplaceError::NotTheOwner)
+        }
 
         self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),
             &AuctionItem{
-                owner: Self::env().caller(),
-                buy_price: 0,
-                seller: None,
-                highest_bid: 0,
-                highest_bidder: None,
-                min_bid: 0,
-                next_min_bid: 0,
-                bid_end_time: 0,
-                on_sale: false,
-                direct: false,
+                owner: caller,
+                ..Default::default()
         });
         let item_count = self.data::<Data>().item_count.saturating_add(1);
         self.data::<Data>().item_count = item_count;
-        self.data::<Data>().market_items.push((address,token_id));
+        self.index_insert(ItemIndex::All, address, token_id.clone());
+        self.index_insert(ItemIndex::Collection(address), address, token_id.clone());
+        self.emit_item_registered_event(address, token_id, caller);
         Ok(())
 
     }
 
-    default fn create_direct_sale(&mut self,address: AccountId, token_id: Id, price: Balance) -> Result<(), MarketplaceError> {
-        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        if item.owner != Self::env().caller() {
-            return Err(MarketplaceError::NotTheOwner)
-        }
-        if item.on_sale == true {
+    default fn remove_market_item(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let caller = Self::env().caller();
+        if item.status != ItemStatus::Unlisted {
             return Err(MarketplaceError::TokenAlreadyOnSale)
         }
+        self.check_token_owner(address, token_id.clone(), &mut item, caller)?;
+
+        // Offers on the item can no longer be accepted, so their escrow is credited back
+        for offerer in self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default() {
+            self.refund_offer(address, token_id.clone(), offerer)?;
+        }
+        self.data::<Data>().items.remove(&(address, token_id.clone()));
+        self.index_remove(ItemIndex::All, address, token_id.clone());
+        self.index_remove(ItemIndex::Collection(address), address, token_id.clone());
+        let item_count = self.data::<Data>().item_count.saturating_sub(1);
+        self.data::<Data>().item_count = item_count;
+        self.emit_item_removed_event(address, token_id, caller);
+
+        Ok(())
+    }
+
+    default fn sync_owner(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let owner = self.get_token_owner(address, token_id.clone()).ok_or(MarketplaceError::TokenDoesNotExist)?;
+
+        if item.status.is_on_sale() {
+            if !self.listing_valid(address, token_id.clone(), &item) {
+                self.invalidate_listing(address, token_id, item)?;
+            }
+        } else if item.status == ItemStatus::Unlisted && item.owner != owner {
+            item.owner = owner;
+            self.data::<Data>().items.insert(&(address, token_id.clone()), &item);
+            self.emit_owner_synced_event(address, token_id, owner);
+        }
+
+        Ok(())
+    }
+
+    default fn create_direct_sale(&mut self,address: AccountId, token_id: Id, price: Balance, currency: Option<AccountId>,
+        expiry: Option<Timestamp>) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.check_transition(&item, ItemStatus::DirectSale)?;
+        self.check_token_owner(address, token_id.clone(), &mut item, Self::env().caller())?;
         if price == 0 {
             return Err(MarketplaceError::IneligibleBuyPrice)
         }
-        // Register NFT contract to marketplace and enable approval to all
-        let this = Self::env().account_id();
+        if expiry.map_or(false, |expiry| expiry <= Self::env().block_timestamp()) {
+            return Err(MarketplaceError::IneligibleListingExpiry)
+        }
+        self.check_currency(currency)?;
+        // Take custody of the token until it is sold or withdrawn
+        self.take_custody(address.clone(),token_id.clone())?;
 
         item.buy_price = price;
         item.seller = Some(Self::env().caller());
-        item.on_sale = true;
-        item.direct = true;
+        self.transition(address, token_id.clone(), &mut item, ItemStatus::DirectSale)?;
+        item.currency = currency;
+        item.expiry = expiry;
 
         self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_listed_event(address, token_id.clone(), item.listing_id, Self::env().caller(), price);
 
-
-/*
-        match PSP34Ref::transfer(&address,this,token_id.clone(),ink::prelude::vec::Vec::new()) {
-            Ok(()) => {
-                item.buy_price = price;
-                item.seller = Some(Self::env().caller());
-                item.on_sale = true;
-                item.direct = true;
-            },
-            Err(_) => return Err(MarketplaceError::TransferToContractFailed)
-        }
-        */
         Ok(())
     }
 
-    default fn create_auction(&mut self,address: AccountId, token_id: Id, price: Balance, min_bid: Balance, duration: Timestamp) -> Result<(), MarketplaceError> {
-        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        if item.owner != Self::env().caller() {
-            return Err(MarketplaceError::NotTheOwner)
-        }
-        if item.on_sale == true {
-            return Err(MarketplaceError::TokenAlreadyOnSale)
-        }
-        if price == 0 {
-            return Err(MarketplaceError::IneligibleBuyPrice)
+    default fn create_auction(&mut self,address: AccountId, token_id: Id, buy_now: Option<Balance>, min_bid: Balance,
+        reserve_price: Balance, reserve_hidden: bool, duration: Timestamp, currency: Option<AccountId>) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.check_transition(&item, ItemStatus::EnglishAuction)?;
+        self.check_token_owner(address, token_id.clone(), &mut item, Self::env().caller())?;
+        if let Some(buy_now) = buy_now {
+            if buy_now == 0 || buy_now < min_bid || buy_now < reserve_price {
+                return Err(MarketplaceError::IneligibleBuyPrice)
+            }
         }
         if duration == 0 {
             return Err(MarketplaceError::IneligibleBidDuration)
         }
-        // Register NFT contract to marketplace and enable approval to all
+        self.check_currency(currency)?;
+        // Take custody of the token until the auction is settled or withdrawn
+        self.take_custody(address.clone(),token_id.clone())?;
 
-        item.buy_price = price;
+        item.buy_now = buy_now;
+        item.currency = currency;
         item.seller = Some(Self::env().caller());
-        item.on_sale = true;
-        item.direct = false;
+        self.transition(address, token_id.clone(), &mut item, ItemStatus::EnglishAuction)?;
         item.min_bid = min_bid;
         item.next_min_bid = min_bid;
-        item.bid_end_time = duration + Self::env().block_timestamp();
+        item.reserve_price = reserve_price;
+        item.reserve_hidden = reserve_hidden;
+        item.bid_end_time = duration.checked_add(Self::env().block_timestamp()).ok_or(MarketplaceError::ArithmeticOverflow)?;
 
         self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_auction_created_event(address, token_id.clone(), item.listing_id, Self::env().caller(), buy_now, min_bid, item.bid_end_time);
 
-        /*
-        let this = Self::env().account_id();
-        match PSP34Ref::transfer(&address,this,token_id.clone(),ink::prelude::vec::Vec::new()) {
-            Ok(()) => {
-                item.buy_price = price;
-                item.seller = Some(Self::env().caller());
-                item.on_sale = true;
-                item.direct = false;
-                item.min_bid = min_bid;
-                item.next_min_bid = min_bid;
-                item.bid_end_time = duration + Self::env().block_timestamp();
-            },
-            Err(_) => return Err(MarketplaceError::TransferToContractFailed)
-        }
-        */
         Ok(())
     }
 
-    #[modifiers(non_reentrant)]
-    default fn close_direct_sale(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
-        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        if item.on_sale == false {
+    default fn set_auction_extension(&mut self, address: AccountId, token_id: Id, extension_window: Timestamp,
+        extension_length: Timestamp, max_end_time: Option<Timestamp>) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if item.seller != Some(Self::env().caller()) {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        if !item.status.is_auction() {
             return Err(MarketplaceError::TokenNotForSale)
         }
-        if item.direct == false {
-            return Err(MarketplaceError::TokenNotForDirectSale)
+        // Sealed auctions run on fixed commit and reveal phases
+        if item.sealed.is_some() {
+            return Err(MarketplaceError::SealedBidAuction)
         }
-        let value = Self::env().transferred_value();
-        if value != item.buy_price {
-            return Err(MarketplaceError::IneligibleBuyPrice)
+        if item.highest_bidder.is_some() {
+            return Err(MarketplaceError::MinimumBidAlreadyMet)
+        }
+        if let Some(max_end_time) = max_end_time {
+            if max_end_time < item.bid_end_time {
+                return Err(MarketplaceError::IneligibleBidDuration)
+            }
         }
 
-        self.finalize_sale(address.clone(),token_id.clone(),value);
+        item.extension_window = extension_window;
+        item.extension_length = extension_length;
+        item.max_end_time = max_end_time;
 
-        self.set_auction_end(address.clone(),token_id.clone())?;
+        self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_auction_extension_set_event(address, token_id, extension_window, extension_length, max_end_time);
 
         Ok(())
     }
 
-    default fn withdraw_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
-        let item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        let caller = Self::env().caller();
-        if item.seller.unwrap() != caller {
+    default fn update_direct_sale_price(&mut self, address: AccountId, token_id: Id, price: Balance) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if item.seller != Some(Self::env().caller()) {
             return Err(MarketplaceError::NotTheOwner)
         }
-        if item.on_sale == false {
-            return Err(MarketplaceError::TokenNotForSale)
+        if item.status != ItemStatus::DirectSale {
+            return Err(MarketplaceError::TokenNotForDirectSale)
         }
-
-        if item.direct == true {
-            match PSP34Ref::transfer(&address,caller,token_id.clone(),ink::prelude::vec::Vec::new()) {
-                Ok(()) => {self.set_auction_end(address.clone(),token_id.clone())?;
-                    Ok(())},
-                Err(_) => return Err(MarketplaceError::TransferToOwnerFailed)
-            }
-        } else {
-            match item.highest_bidder {
-                Some(highest_bidder) => return Err(MarketplaceError::MinimumBidAlreadyMet),
-                None => {match PSP34Ref::transfer(&address,caller,token_id.clone(),ink::prelude::vec::Vec::new()) {
-                        Ok(()) => {self.set_auction_end(address.clone(),token_id.clone())?;
-                            Ok(())},
-                        Err(_) => return Err(MarketplaceError::TransferToOwnerFailed)
-                    }
-                }
-            }
+        // Dutch auctions derive their price from the schedule instead
+        if item.dutch.is_some() {
+            return Err(MarketplaceError::PriceNotEditable)
+        }
+        if price == 0 {
+            return Err(MarketplaceError::IneligibleBuyPrice)
         }
+
+        item.buy_price = price;
+
+        self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_direct_sale_updated_event(address, token_id, Self::env().caller(), price);
+
+        Ok(())
     }
 
-    default fn make_bid(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>  {
-        let item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        if item.on_sale == false {
+    default fn update_auction_params(&mut self, address: AccountId, token_id: Id, buy_now: Option<Balance>, min_bid: Balance,
+        bid_end_time: Timestamp) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if item.seller != Some(Self::env().caller()) {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        if !item.status.is_auction() {
             return Err(MarketplaceError::TokenNotForSale)
         }
-
+        if item.sealed.is_some() {
+            return Err(MarketplaceError::SealedBidAuction)
+        }
+        // Bidders committed against the current terms, and a later end time would keep their bid locked
+        if item.highest_bidder.is_some() {
+            return Err(MarketplaceError::AuctionHasBids)
+        }
         if item.bid_end_time < Self::env().block_timestamp() {
             return Err(MarketplaceError::AuctionExpired)
         }
-        let value = Self::env().transferred_value();
-
-        if value < item.next_min_bid {
-            return Err(MarketplaceError::MinimumBidNotMet)
+        if bid_end_time <= Self::env().block_timestamp() || item.max_end_time.map_or(false, |max_end_time| bid_end_time > max_end_time) {
+            return Err(MarketplaceError::IneligibleBidDuration)
+        }
+        if let Some(buy_now) = buy_now {
+            if buy_now == 0 || buy_now < min_bid || buy_now < item.reserve_price {
+                return Err(MarketplaceError::IneligibleBuyPrice)
+            }
         }
 
-        if value >= item.buy_price {
-            let Ok((highest_bid,highest_bidder)) = self.update_highest_bid(address.clone(),token_id.clone(),Self::env().caller(),value) else 
-            {return Err(MarketplaceError::BidNotUpdated)};
-            self.finalize_sale(address.clone(),token_id.clone(),value);
+        item.buy_now = buy_now;
+        item.min_bid = min_bid;
+        item.next_min_bid = min_bid;
+        item.bid_end_time = bid_end_time;
 
-            self.set_auction_end(address.clone(),token_id.clone())?;
+        self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_auction_updated_event(address, token_id, Self::env().caller(), buy_now, min_bid, bid_end_time);
 
-            Ok(())
-        } else {
-            let Ok((highest_bid,highest_bidder)) = self.update_highest_bid(address.clone(),token_id.clone(),Self::env().caller(),value) else 
-            {return Err(MarketplaceError::BidNotUpdated)};
-            let next_min_bid = self.calculate_next_minimum_bid(address.clone(),token_id.clone());
-            self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),
-            &AuctionItem{
-                owner: item.owner,
-                buy_price: item.buy_price,
-                seller: item.seller,
-                highest_bid: highest_bid,
-                highest_bidder: highest_bidder,
-                min_bid: item.min_bid,
-                next_min_bid: next_min_bid,
-                bid_end_time: item.bid_end_time,
-                on_sale: item.on_sale,
-                direct: item.direct,
-        });
-            Ok(())
-        }
+        Ok(())
     }
 
-    default fn settle_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
-        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).unwrap();
-        if item.on_sale == false {
-            return Err(MarketplaceError::TokenNotForSale)
-        }
-        if item.direct == true {
-            return Err(MarketplaceError::TokenOnlyForDirectSale)
-        }
-        if item.bid_end_time >= Self::env().block_timestamp(){
-            return Err(MarketplaceError::AuctionOngoing)
+    default fn create_sealed_auction(&mut self, address: AccountId, token_id: Id, min_bid: Balance,
+        commit_duration: Timestamp, reveal_duration: Timestamp, vickrey: bool, max_bids: u32) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.check_transition(&item, ItemStatus::SealedAuction)?;
+        self.check_token_owner(address, token_id.clone(), &mut item, Self::env().caller())?;
+        if commit_duration == 0 || reveal_duration == 0 {
+            return Err(MarketplaceError::IneligibleBidDuration)
         }
-
-        if item.highest_bidder == None {
-            return Err(MarketplaceError::NoValidBids)
+        if max_bids == 0 || max_bids as usize > MAX_SEALED_BIDS {
+            return Err(MarketplaceError::IneligibleBidLimit)
         }
+        // Take custody of the token until the auction is settled or withdrawn
+        self.take_custody(address.clone(),token_id.clone())?;
 
-        let value = item.highest_bid;
-
-        self.finalize_sale(address.clone(),token_id.clone(),value);
+        let commit_end_time = commit_duration.checked_add(Self::env().block_timestamp()).ok_or(MarketplaceError::ArithmeticOverflow)?;
+        item.seller = Some(Self::env().caller());
+        self.transition(address, token_id.clone(), &mut item, ItemStatus::SealedAuction)?;
+        item.min_bid = min_bid;
+        item.next_min_bid = min_bid;
+        item.bid_end_time = commit_end_time.checked_add(reveal_duration).ok_or(MarketplaceError::ArithmeticOverflow)?;
+        item.sealed = Some(SealedAuction {
+            commit_end_time,
+            vickrey,
+            second_highest_bid: 0,
+            max_bids,
+            unrevealed_penalty: self.data::<Data>().unrevealed_penalty,
+        });
 
-        self.set_auction_end(address.clone(),token_id.clone())?;
+        self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_sealed_auction_created_event(address, token_id, Self::env().caller(), min_bid, commit_end_time, item.bid_end_time, vickrey);
 
         Ok(())
-
     }
 
-    default fn get_fee_recipient(&self) -> AccountId {
+    default fn commit_bid(&mut self, address: AccountId, token_id: Id, commitment: [u8; 32]) -> Result<(), MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let sealed = item.sealed.clone().ok_or(MarketplaceError::NotSealedBidAuction)?;
+        let caller = Self::env().caller();
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        self.check_listing_valid(address, token_id.clone(), &item)?;
+        if item.seller == Some(caller) {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        if sealed.commit_end_time < Self::env().block_timestamp() {
+            return Err(MarketplaceError::CommitPhaseOver)
+        }
+        let deposit = Self::env().transferred_value();
+        if deposit < item.min_bid || deposit == 0 {
+            return Err(MarketplaceError::MinimumBidNotMet)
+        }
+        if self.data::<Data>().sealed_bids.get(&(address, token_id.clone(), caller)).is_some() {
+            return Err(MarketplaceError::BidAlreadyCommitted)
+        }
+
+        let mut bidders = self.data::<Data>().sealed_bidders.get(&(address, token_id.clone())).unwrap_or_default();
+        if bidders.len() >= sealed.max_bids as usize {
+            return Err(MarketplaceError::TooManyBids)
+        }
+        bidders.push(caller);
+
+        self.data::<Data>().sealed_bidders.insert(&(address, token_id.clone()), &bidders);
+        self.data::<Data>().sealed_bids.insert(&(address, token_id.clone(), caller), &SealedBid {
+            commitment,
+            deposit,
+            revealed: None,
+        });
+        self.emit_bid_committed_event(address, token_id, caller, deposit);
+
+        Ok(())
+    }
+
+    default fn reveal_bid(&mut self, address: AccountId, token_id: Id, amount: Balance, salt: [u8; 32]) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let mut sealed = item.sealed.clone().ok_or(MarketplaceError::NotSealedBidAuction)?;
+        let caller = Self::env().caller();
+        let now = Self::env().block_timestamp();
+        if now <= sealed.commit_end_time {
+            return Err(MarketplaceError::RevealPhaseNotStarted)
+        }
+        if item.bid_end_time < now {
+            return Err(MarketplaceError::AuctionExpired)
+        }
+
+        let mut bid = self.data::<Data>().sealed_bids.get(&(address, token_id.clone(), caller))
+            .ok_or(MarketplaceError::BidNotFound)?;
+        if bid.revealed.is_some() {
+            return Err(MarketplaceError::BidAlreadyRevealed)
+        }
+        if self.compute_bid_commitment(caller, amount, salt) != bid.commitment || amount > bid.deposit {
+            return Err(MarketplaceError::InvalidReveal)
+        }
+
+        bid.revealed = Some(amount);
+        self.data::<Data>().sealed_bids.insert(&(address, token_id.clone(), caller), &bid);
+
+        // Bids under the minimum are revealed for a full refund but cannot win
+        if amount >= item.min_bid {
+            if item.highest_bidder.is_none() || amount > item.highest_bid {
+                if item.highest_bidder.is_some() {
+                    sealed.second_highest_bid = item.highest_bid;
+                }
+                item.highest_bid = amount;
+                item.highest_bidder = Some(caller);
+            } else if amount > sealed.second_highest_bid {
+                sealed.second_highest_bid = amount;
+            }
+            item.sealed = Some(sealed);
+            self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        }
+        self.emit_bid_revealed_event(address, token_id, caller, amount);
+
+        Ok(())
+    }
+
+    default fn compute_bid_commitment(&self, bidder: AccountId, amount: Balance, salt: [u8; 32]) -> [u8; 32] {
+        Self::env().hash_encoded::<Blake2x256, _>(&(bidder, amount, salt))
+    }
+
+    default fn get_sealed_bid(&self, address: AccountId, token_id: Id, bidder: AccountId) -> Option<SealedBid> {
+        self.data::<Data>().sealed_bids.get(&(address, token_id, bidder))
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_unrevealed_penalty(&mut self, penalty: u16) -> Result<(), MarketplaceError> {
+        if penalty > 10000 {
+            return Err(MarketplaceError::IneligiblePenalty)
+        }
+        self.data::<Data>().unrevealed_penalty = penalty;
+        Ok(())
+    }
+
+    default fn get_unrevealed_penalty(&self) -> u16 {
+        self.data::<Data>().unrevealed_penalty
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn close_direct_sale(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        if item.status != ItemStatus::DirectSale {
+            return Err(MarketplaceError::TokenNotForDirectSale)
+        }
+        self.check_listing_valid(address, token_id.clone(), &item)?;
+        let value = Self::env().transferred_value();
+        let price = self.get_direct_sale_price(&item)?;
+        match item.currency {
+            Some(currency) => {
+                if value != 0 {
+                    return Err(MarketplaceError::CurrencyMismatch)
+                }
+                self.receive_payment(currency, Self::env().caller(), price)?;
+            },
+            None => {
+                // Only Dutch auctions accept overpayment, as their price keeps moving
+                if value < price || (item.dutch.is_none() && value != price) {
+                    return Err(MarketplaceError::IneligibleBuyPrice)
+                }
+            }
+        }
+
+        self.finalize_sale(address.clone(),token_id.clone(),Self::env().caller(),price)?;
+
+        self.set_auction_end(address.clone(),token_id.clone())?;
+
+        if item.currency.is_none() && value > price {
+            Self::env().transfer(Self::env().caller(), value - price).map_err(|_| MarketplaceError::RefundFailed)?;
+        }
+
+        Ok(())
+    }
+
+    default fn create_dutch_auction(&mut self, address: AccountId, token_id: Id, start_price: Balance, end_price: Balance,
+        start_time: Timestamp, duration: Timestamp, step: Timestamp) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.check_transition(&item, ItemStatus::DirectSale)?;
+        self.check_token_owner(address, token_id.clone(), &mut item, Self::env().caller())?;
+        if start_price == 0 || end_price > start_price {
+            return Err(MarketplaceError::IneligibleBuyPrice)
+        }
+        if duration == 0 || step > duration {
+            return Err(MarketplaceError::IneligibleBidDuration)
+        }
+        let start_time = core::cmp::max(start_time, Self::env().block_timestamp());
+
+        // Take custody of the token until it is sold or withdrawn
+        self.take_custody(address.clone(),token_id.clone())?;
+
+        item.buy_price = start_price;
+        item.seller = Some(Self::env().caller());
+        self.transition(address, token_id.clone(), &mut item, ItemStatus::DirectSale)?;
+        item.dutch = Some(DutchAuction {
+            start_price,
+            end_price,
+            start_time,
+            duration,
+            step,
+        });
+
+        self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),&item);
+        self.emit_dutch_auction_created_event(address, token_id, Self::env().caller(), start_price, end_price, start_time, duration);
+
+        Ok(())
+    }
+
+    default fn get_current_price(&self, address: AccountId, token_id: Id) -> Option<Balance> {
+        let item = self.data::<Data>().items.get(&(address, token_id))?;
+        if !item.status.is_on_sale() {
+            return None
+        }
+        // The price `close_direct_sale` would charge, so the UI never quotes what cannot be bought
+        self.get_direct_sale_price(&item).ok()
+    }
+
+    default fn withdraw_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let caller = Self::env().caller();
+        if item.seller != Some(caller) {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+
+        // Sealed deposits can only be released once the reveal phase is over
+        if item.sealed.is_some() && item.highest_bidder.is_none()
+            && self.data::<Data>().sealed_bidders.get(&(address, token_id.clone())).is_some() {
+            if item.bid_end_time >= Self::env().block_timestamp() {
+                return Err(MarketplaceError::AuctionOngoing)
+            }
+            self.release_sealed_deposits(address, token_id.clone(), None, 0, caller);
+        }
+
+        if item.status == ItemStatus::DirectSale {
+            match self.transfer_token(address,caller,token_id.clone()) {
+                Ok(()) => {self.set_auction_end(address.clone(),token_id.clone())?;
+                    self.emit_listing_withdrawn_event(address, token_id, item.listing_id, caller);
+                    Ok(())},
+                Err(_) => return Err(MarketplaceError::TransferToOwnerFailed)
+            }
+        } else {
+            match item.highest_bidder {
+                Some(highest_bidder) => return Err(MarketplaceError::MinimumBidAlreadyMet),
+                None => {match self.transfer_token(address,caller,token_id.clone()) {
+                        Ok(()) => {self.set_auction_end(address.clone(),token_id.clone())?;
+                            self.emit_listing_withdrawn_event(address, token_id, item.listing_id, caller);
+                            Ok(())},
+                        Err(_) => return Err(MarketplaceError::TransferToOwnerFailed)
+                    }
+                }
+            }
+        }
+    }
+
+    default fn clear_expired_listing(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if !self.listing_expired(&item) {
+            return Err(MarketplaceError::ListingNotExpired)
+        }
+        let seller = item.seller.ok_or(MarketplaceError::TokenNotForSale)?;
+
+        // Escrowed tokens go back to the seller, anything else never left their wallet
+        if self.index_contains(ItemIndex::Escrowed, address, token_id.clone()) {
+            self.transfer_token(address,seller,token_id.clone())
+                .map_err(|_| MarketplaceError::TransferToOwnerFailed)?;
+        }
+        self.set_auction_end(address.clone(),token_id.clone())?;
+        self.emit_listing_expired_event(address, token_id, seller);
+
+        Ok(())
+    }
+
+    default fn is_listing_expired(&self, address: AccountId, token_id: Id) -> bool {
+        self.data::<Data>().items.get(&(address, token_id))
+            .map_or(false, |item| self.listing_expired(&item))
+    }
+
+    default fn listing_health(&self, address: AccountId, token_id: Id) -> ListingHealth {
+        let Some(item) = self.data::<Data>().items.get(&(address, token_id.clone())) else {
+            return ListingHealth::NotListed
+        };
+        if !item.status.is_on_sale() {
+            return ListingHealth::NotListed
+        }
+        if !self.listing_valid(address, token_id.clone(), &item) {
+            return ListingHealth::TokenMoved
+        }
+        // Escrowed tokens are moved by the marketplace itself, so only other listings need the seller's approval
+        if !self.index_contains(ItemIndex::Escrowed, address, token_id.clone())
+            && !item.seller.map_or(false, |seller| self.is_approved(address, token_id, seller)) {
+            return ListingHealth::ApprovalMissing
+        }
+        if self.listing_expired(&item) {
+            return ListingHealth::Expired
+        }
+        if item.status.is_auction() && item.bid_end_time < Self::env().block_timestamp() {
+            return ListingHealth::AuctionEnded
+        }
+
+        ListingHealth::Healthy
+    }
+
+    default fn make_bid(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>  {
+        let item = self.get_open_auction(address, token_id.clone())?;
+        if item.currency.is_some() {
+            return Err(MarketplaceError::CurrencyMismatch)
+        }
+
+        self.place_bid(address, token_id, item, Self::env().caller(), Self::env().transferred_value())
+    }
+
+    default fn make_token_bid(&mut self, address: AccountId, token_id: Id, amount: Balance) -> Result<(), MarketplaceError> {
+        let item = self.get_open_auction(address, token_id.clone())?;
+        let currency = item.currency.ok_or(MarketplaceError::CurrencyMismatch)?;
+        if Self::env().transferred_value() != 0 {
+            return Err(MarketplaceError::CurrencyMismatch)
+        }
+
+        let bidder = Self::env().caller();
+        self.receive_payment(currency, bidder, amount)?;
+        self.place_bid(address, token_id, item, bidder, amount)
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn settle_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        if item.status == ItemStatus::DirectSale {
+            return Err(MarketplaceError::TokenOnlyForDirectSale)
+        }
+        if item.bid_end_time >= Self::env().block_timestamp(){
+            return Err(MarketplaceError::AuctionOngoing)
+        }
+
+        let seller = item.seller.ok_or(MarketplaceError::TokenNotForSale)?;
+        // Without a winner the sealed deposits are released and the token goes back to the seller
+        let Some(highest_bidder) = item.highest_bidder else {
+            return self.return_unsold_auction(address, token_id, item, seller)
+        };
+
+        if item.highest_bid < item.reserve_price {
+            // The seller gets the grace period to accept the bid, after that the bidder is released
+            if !self.reserve_grace_over(&item) {
+                return Err(MarketplaceError::ReserveNotMet)
+            }
+            return self.return_unsold_auction(address, token_id, item, seller)
+        }
+
+        let value = self.get_clearing_price(&item);
+
+        let market_fees = self.finalize_sale(address.clone(),token_id.clone(),highest_bidder,value)?;
+        self.pay_keeper_reward(address.clone(),token_id.clone(),Self::env().caller(),market_fees,item.currency)?;
+        if item.sealed.is_some() {
+            self.release_sealed_deposits(address, token_id.clone(), Some(highest_bidder), value, seller);
+        }
+
+        self.set_auction_end(address.clone(),token_id.clone())?;
+        self.emit_auction_settled_event(address, token_id, item.listing_id, item.seller, highest_bidder, value);
+
+        Ok(())
+
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_keeper_reward(&mut self, reward: u16) -> Result<(), MarketplaceError> {
+        if reward > 10000 {
+            return Err(MarketplaceError::IneligibleKeeperReward)
+        }
+        self.data::<Data>().keeper_reward = reward;
+        Ok(())
+    }
+
+    default fn get_keeper_reward(&self) -> u16 {
+        self.data::<Data>().keeper_reward
+    }
+
+    default fn get_settleable_auctions(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::Active, offset, limit)
+            .into_iter()
+            .filter(|(address, token_id)| {
+                self.data::<Data>().items.get(&(*address, token_id.clone()))
+                    .map_or(false, |item| self.is_settleable(&item))
+            })
+            .collect()
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn accept_highest_bid(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let item = self.get_ended_auction(address, token_id.clone())?;
+        let highest_bidder = item.highest_bidder.ok_or(MarketplaceError::NoValidBids)?;
+
+        let value = self.get_clearing_price(&item);
+        self.finalize_sale(address.clone(),token_id.clone(),highest_bidder,value)?;
+        if item.sealed.is_some() {
+            self.release_sealed_deposits(address, token_id.clone(), Some(highest_bidder), value, Self::env().caller());
+        }
+
+        self.set_auction_end(address.clone(),token_id.clone())?;
+        self.emit_auction_settled_event(address, token_id, item.listing_id, item.seller, highest_bidder, value);
+
+        Ok(())
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn decline_highest_bid(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let item = self.get_ended_auction(address, token_id.clone())?;
+        if item.highest_bidder.is_none() {
+            return Err(MarketplaceError::NoValidBids)
+        }
+        if item.highest_bid >= item.reserve_price {
+            return Err(MarketplaceError::ReserveAlreadyMet)
+        }
+
+        self.return_unsold_auction(address, token_id, item, Self::env().caller())
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_reserve_grace_period(&mut self, period: Timestamp) -> Result<(), MarketplaceError> {
+        self.data::<Data>().reserve_grace_period = period;
+        Ok(())
+    }
+
+    default fn get_reserve_grace_period(&self) -> Timestamp {
+        self.data::<Data>().reserve_grace_period
+    }
+
+    default fn get_fee_recipient(&self) -> AccountId {
         self.data::<Data>().market_fee_recipient
     }
 
-    #[modifiers(only_owner)]
-    default fn set_marketplace_fee(&mut self, fee: u16) -> Result<(), MarketplaceError> {
-        self.data::<Data>().fee = fee;
+    #[modifiers(only_owner)]
+    default fn set_marketplace_fee(&mut self, fee: u16) -> Result<(), MarketplaceError> {
+        // Fee and royalties are both taken out of the sale price, so together they cannot exceed it
+        if u32::from(fee) + u32::from(self.data::<Data>().max_royalty) > 10000 {
+            return Err(MarketplaceError::FeeTooHigh)
+        }
+        self.data::<Data>().fee = fee;
+        Ok(())
+    }
+
+    default fn get_marketplace_fee(&self) -> u16 {
+        self.data::<Data>().fee
+    }
+
+    #[modifiers(only_owner)]
+    default fn set_max_royalty(&mut self, max_royalty: u16) -> Result<(), MarketplaceError> {
+        if u32::from(max_royalty) + u32::from(self.data::<Data>().fee) > 10000 {
+            return Err(MarketplaceError::RoyaltyTooHigh)
+        }
+        self.data::<Data>().max_royalty = max_royalty;
+        Ok(())
+    }
+
+    default fn get_max_royalty(&self) -> u16 {
+        self.data::<Data>().max_royalty
+    }
+
+    default fn get_item_count(&self) -> u64 {
+        self.data::<Data>().item_count       
+    }
+
+    default fn get_market_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::All, offset, limit)
+    }
+
+    default fn get_collection_items(&self, address: AccountId, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::Collection(address), offset, limit)
+    }
+
+    default fn get_seller_items(&self, seller: AccountId, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::Seller(seller), offset, limit)
+    }
+
+    default fn get_active_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::Active, offset, limit)
+    }
+
+    default fn get_escrowed_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        self.index_page(ItemIndex::Escrowed, offset, limit)
+    }
+
+    default fn get_index_length(&self, index: ItemIndex) -> u64 {
+        self.data::<Data>().index_lengths.get(&index).unwrap_or(0)
+    }
+
+    default fn make_offer(&mut self, address: AccountId, token_id: Id, expiry: Timestamp) -> Result<(), MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone()))
+            .ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.check_collection_active(address)?;
+        let caller = Self::env().caller();
+        if item.owner == caller {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        let value = Self::env().transferred_value();
+        if value == 0 {
+            return Err(MarketplaceError::IneligibleOfferAmount)
+        }
+        let now = Self::env().block_timestamp();
+        if expiry <= now {
+            return Err(MarketplaceError::IneligibleOfferExpiry)
+        }
+        // Expired offers are dropped first and their escrow credited back
+        for offerer in self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default() {
+            if self.data::<Data>().offers.get(&(address, token_id.clone(), offerer)).map_or(false, |offer| offer.expiry < now) {
+                self.refund_offer(address, token_id.clone(), offerer)?;
+            }
+        }
+        if self.data::<Data>().offers.get(&(address, token_id.clone(), caller)).is_some() {
+            return Err(MarketplaceError::OfferAlreadyExists)
+        }
+
+        // A full list evicts its lowest offer, so cheap offers cannot keep better ones out
+        let offerers = self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default();
+        if offerers.len() >= MAX_OFFERS_PER_TOKEN {
+            let (lowest, amount) = offerers.iter()
+                .filter_map(|offerer| {
+                    self.data::<Data>().offers.get(&(address, token_id.clone(), *offerer))
+                        .map(|offer| (*offerer, offer.amount))
+                })
+                .min_by_key(|(_, amount)| *amount)
+                .ok_or(MarketplaceError::TooManyOffers)?;
+            if amount >= value {
+                return Err(MarketplaceError::TooManyOffers)
+            }
+            self.refund_offer(address, token_id.clone(), lowest)?;
+        }
+        let mut offerers = self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default();
+        offerers.push(caller);
+
+        self.data::<Data>().offerers.insert(&(address, token_id.clone()), &offerers);
+        self.data::<Data>().offers.insert(&(address, token_id.clone(), caller), &Offer { amount: value, expiry });
+        self.emit_offer_made_event(address, token_id, caller, value, expiry);
+
+        Ok(())
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn cancel_offer(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError> {
+        let caller = Self::env().caller();
+        let offer = self.remove_offer(address, token_id.clone(), caller)?;
+
+        Self::env().transfer(caller, offer.amount).map_err(|_| MarketplaceError::TransferToBidderFailed)?;
+        self.emit_offer_cancelled_event(address, token_id, caller, offer.amount);
+
+        Ok(())
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn accept_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<(), MarketplaceError> {
+        let mut item = self.data::<Data>().items.get(&(address, token_id.clone()))
+            .ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let caller = Self::env().caller();
+        if item.status != ItemStatus::Unlisted {
+            return Err(MarketplaceError::TokenAlreadyOnSale)
+        }
+        self.check_token_owner(address, token_id.clone(), &mut item, caller)?;
+
+        let offer = self.remove_offer(address, token_id.clone(), offerer)?;
+        if offer.expiry < Self::env().block_timestamp() {
+            return Err(MarketplaceError::OfferExpired)
+        }
+
+        let breakdown = self.get_sales_breakdown(address, token_id.clone(), offer.amount)?;
+        self.transfer_token(address,offerer,token_id.clone())
+            .map_err(|_| MarketplaceError::TokenTransferFailed)?;
+        self.distribute_sale_proceeds(address, token_id.clone(), caller, offerer, offer.amount, breakdown, None)?;
+        self.set_auction_end(address, token_id.clone())?;
+        self.emit_offer_accepted_event(address, token_id, caller, offerer, offer.amount);
+
+        Ok(())
+    }
+
+    default fn get_offer(&self, address: AccountId, token_id: Id, offerer: AccountId) -> Option<Offer> {
+        self.data::<Data>().offers.get(&(address, token_id, offerer))
+    }
+
+    default fn get_offers(&self, address: AccountId, token_id: Id, offset: u64, limit: u64) -> Vec<(AccountId, Offer)> {
+        let offerers = self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default();
+        offerers
+            .into_iter()
+            .skip(offset as usize)
+            .take(limit as usize)
+            .filter_map(|offerer| {
+                self.data::<Data>().offers.get(&(address, token_id.clone(), offerer))
+                    .map(|offer| (offerer, offer))
+            })
+            .collect()
+    }
+
+    default fn make_collection_offer(&mut self, address: AccountId, price: Balance, quantity: u32, expiry: Timestamp) -> Result<u64, MarketplaceError> {
+        self.check_collection_active(address)?;
+        if price == 0 || quantity == 0 {
+            return Err(MarketplaceError::IneligibleOfferAmount)
+        }
+        if Some(Self::env().transferred_value()) != price.checked_mul(u128::from(quantity)) {
+            return Err(MarketplaceError::IneligibleOfferAmount)
+        }
+        if expiry <= Self::env().block_timestamp() {
+            return Err(MarketplaceError::IneligibleOfferExpiry)
+        }
+
+        // Expired offers are dropped first and their escrow credited back
+        let now = Self::env().block_timestamp();
+        let (expired, mut offers): (Vec<_>, Vec<_>) = self.data::<Data>().collection_offers.get(&address).unwrap_or_default()
+            .into_iter()
+            .partition(|offer| offer.expiry < now);
+        for offer in expired {
+            self.refund_collection_offer(address, offer)?;
+        }
+        // A full book evicts its lowest offer, so cheap offers cannot keep better ones out
+        if offers.len() >= MAX_COLLECTION_OFFERS {
+            let index = offers.iter()
+                .enumerate()
+                .min_by_key(|(_, offer)| offer.price)
+                .map(|(index, _)| index)
+                .ok_or(MarketplaceError::TooManyOffers)?;
+            if offers[index].price >= price {
+                return Err(MarketplaceError::TooManyOffers)
+            }
+            let evicted = offers.remove(index);
+            self.refund_collection_offer(address, evicted)?;
+        }
+
+        let offer_id = self.data::<Data>().collection_offer_count.saturating_add(1);
+        let offerer = Self::env().caller();
+        offers.push(CollectionOffer { id: offer_id, offerer, price, quantity, expiry });
+
+        self.data::<Data>().collection_offers.insert(&address, &offers);
+        self.data::<Data>().collection_offer_count = offer_id;
+        self.emit_collection_offer_made_event(address, offer_id, offerer, price, quantity, expiry);
+
+        Ok(offer_id)
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn cancel_collection_offer(&mut self, address: AccountId, offer_id: u64) -> Result<(), MarketplaceError> {
+        let caller = Self::env().caller();
+        let mut offers = self.data::<Data>().collection_offers.get(&address).unwrap_or_default();
+        let index = offers.iter()
+            .position(|offer| offer.id == offer_id && offer.offerer == caller)
+            .ok_or(MarketplaceError::OfferNotFound)?;
+        let offer = offers.remove(index);
+
+        self.data::<Data>().collection_offers.insert(&address, &offers);
+        let refund = offer.price.checked_mul(u128::from(offer.quantity)).ok_or(MarketplaceError::ArithmeticOverflow)?;
+        Self::env().transfer(caller, refund).map_err(|_| MarketplaceError::TransferToBidderFailed)?;
+        self.emit_collection_offer_cancelled_event(address, offer_id, caller, refund);
+
+        Ok(())
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn fill_collection_offer(&mut self, address: AccountId, token_id: Id, offer_id: u64) -> Result<(), MarketplaceError> {
+        if !self.check_collection_exists(address.clone()) {
+            return Err(MarketplaceError::CollectionNotRegisteredToMarketplace)
+        }
+        let caller = Self::env().caller();
+        let owner = self.get_token_owner(address, token_id.clone()).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if owner != caller {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+
+        // Only the offer the seller picked is filled, so a cancelled or evicted offer cannot be swapped for a worse one
+        let now = Self::env().block_timestamp();
+        let mut offers = self.data::<Data>().collection_offers.get(&address).unwrap_or_default();
+        let index = offers.iter()
+            .position(|offer| offer.id == offer_id)
+            .ok_or(MarketplaceError::OfferNotFound)?;
+        if offers[index].offerer == caller {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        if offers[index].expiry < now {
+            return Err(MarketplaceError::OfferExpired)
+        }
+
+        let offer = offers[index].clone();
+        if offer.quantity <= 1 {
+            offers.remove(index);
+        } else {
+            offers[index].quantity = offer.quantity.saturating_sub(1);
+        }
+        self.data::<Data>().collection_offers.insert(&address, &offers);
+
+        let breakdown = self.get_sales_breakdown(address, token_id.clone(), offer.price)?;
+        self.transfer_token(address,offer.offerer,token_id.clone())
+            .map_err(|_| MarketplaceError::TokenTransferFailed)?;
+        self.distribute_sale_proceeds(address, token_id.clone(), caller, offer.offerer, offer.price, breakdown, None)?;
+        if self.check_token_exists(address, token_id.clone()) {
+            self.set_auction_end(address, token_id.clone())?;
+        }
+        self.emit_collection_offer_filled_event(address, offer.id, token_id, caller, offer.offerer, offer.price, offer.quantity.saturating_sub(1));
+
+        Ok(())
+    }
+
+    default fn get_collection_offers(&self, address: AccountId) -> Vec<CollectionOffer> {
+        self.data::<Data>().collection_offers.get(&address).unwrap_or_default()
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn withdraw_funds(&mut self) -> Result<Balance, MarketplaceError> {
+        let caller = Self::env().caller();
+        let amount = self.data::<Data>().pending_withdrawals.get(&caller).unwrap_or(0);
+        if amount == 0 {
+            return Err(MarketplaceError::NoFundsToWithdraw)
+        }
+
+        self.data::<Data>().pending_withdrawals.remove(&caller);
+        let total = self.data::<Data>().total_pending_withdrawals.saturating_sub(amount);
+        self.data::<Data>().total_pending_withdrawals = total;
+        Self::env().transfer(caller, amount).map_err(|_| MarketplaceError::WithdrawalFailed)?;
+        self.emit_funds_withdrawn_event(caller, amount);
+
+        Ok(amount)
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn withdraw_token_funds(&mut self, currency: AccountId) -> Result<Balance, MarketplaceError> {
+        let caller = Self::env().caller();
+        let amount = self.data::<Data>().pending_token_withdrawals.get(&(caller, currency)).unwrap_or(0);
+        if amount == 0 {
+            return Err(MarketplaceError::NoFundsToWithdraw)
+        }
+
+        self.data::<Data>().pending_token_withdrawals.remove(&(caller, currency));
+        let liabilities = self.data::<Data>().token_liabilities.get(&currency).unwrap_or(0);
+        self.data::<Data>().token_liabilities.insert(&currency, &liabilities.saturating_sub(amount));
+        PSP22Ref::transfer(&currency, caller, amount, ink::prelude::vec::Vec::new())
+            .map_err(|_| MarketplaceError::WithdrawalFailed)?;
+        self.emit_token_funds_withdrawn_event(caller, currency, amount);
+
+        Ok(amount)
+    }
+
+    default fn get_pending_token_withdrawal(&self, account: AccountId, currency: AccountId) -> Balance {
+        self.data::<Data>().pending_token_withdrawals.get(&(account, currency)).unwrap_or(0)
+    }
+
+    default fn get_token_liabilities(&self, currency: AccountId) -> Balance {
+        self.data::<Data>().token_liabilities.get(&currency).unwrap_or(0)
+    }
+
+    #[modifiers(only_owner)]
+    default fn add_currency(&mut self, currency: AccountId) -> Result<(), MarketplaceError> {
+        if self.data::<Data>().currencies.contains(&currency) {
+            return Err(MarketplaceError::CurrencyAlreadyAccepted)
+        }
+        self.data::<Data>().currencies.push(currency);
+        Ok(())
+    }
+
+    /// Listings already priced in the currency can still be bought and settled.
+    #[modifiers(only_owner)]
+    default fn remove_currency(&mut self, currency: AccountId) -> Result<(), MarketplaceError> {
+        if !self.data::<Data>().currencies.contains(&currency) {
+            return Err(MarketplaceError::CurrencyNotAccepted)
+        }
+        self.data::<Data>().currencies.retain(|accepted| *accepted != currency);
+        Ok(())
+    }
+
+    default fn get_accepted_currencies(&self) -> Vec<AccountId> {
+        self.data::<Data>().currencies.clone()
+    }
+
+    default fn get_pending_withdrawal(&self, account: AccountId) -> Balance {
+        self.data::<Data>().pending_withdrawals.get(&account).unwrap_or(0)
+    }
+
+    default fn get_total_liabilities(&self) -> Balance {
+        self.data::<Data>().total_pending_withdrawals
+    }
+
+    default fn batch_create_direct_sale(&mut self, items: Vec<(AccountId, Id, Balance)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError> {
+        if items.len() > MAX_BATCH_SIZE {
+            return Err(MarketplaceError::BatchTooLarge)
+        }
+        let mut results = Vec::new();
+        for (address, token_id, price) in items {
+            let mut result = Ok(());
+            if !self.check_token_exists(address, token_id.clone()) {
+                result = self.create_market_item(address, token_id.clone());
+            }
+            if result.is_ok() {
+                result = self.create_direct_sale(address, token_id, price, None, None);
+            }
+            match result {
+                Err(error) if atomic => return Err(error),
+                result => results.push(result),
+            }
+        }
+
+        Ok(results)
+    }
+
+    default fn batch_withdraw(&mut self, items: Vec<(AccountId, Id)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError> {
+        if items.len() > MAX_BATCH_SIZE {
+            return Err(MarketplaceError::BatchTooLarge)
+        }
+        let mut results = Vec::new();
+        for (address, token_id) in items {
+            let result = self.withdraw_auction(address, token_id);
+            match result {
+                Err(error) if atomic => return Err(error),
+                result => results.push(result),
+            }
+        }
+
+        Ok(results)
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn batch_buy(&mut self, items: Vec<(AccountId, Id)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError> {
+        if items.len() > MAX_BATCH_SIZE {
+            return Err(MarketplaceError::BatchTooLarge)
+        }
+        let buyer = Self::env().caller();
+        let mut remaining = Self::env().transferred_value();
+        let mut results = Vec::new();
+        for (address, token_id) in items {
+            let result = self.buy_item(address, token_id, buyer, remaining).map(|price| remaining -= price);
+            match result {
+                Err(error) if atomic => return Err(error),
+                result => results.push(result),
+            }
+        }
+
+        // Whatever was not spent goes back to the buyer
+        if remaining > 0 {
+            Self::env().transfer(buyer, remaining).map_err(|_| MarketplaceError::RefundFailed)?;
+        }
+
+        Ok(results)
+    }
+
+    default fn create_bundle(&mut self, items: Vec<(AccountId, Id)>, price: Balance) -> Result<u64, MarketplaceError> {
+        if price == 0 {
+            return Err(MarketplaceError::IneligibleBuyPrice)
+        }
+        self.list_bundle(items, price, true, 0, 0)
+    }
+
+    default fn create_bundle_auction(&mut self, items: Vec<(AccountId, Id)>, min_bid: Balance, duration: Timestamp) -> Result<u64, MarketplaceError> {
+        if duration == 0 {
+            return Err(MarketplaceError::IneligibleBidDuration)
+        }
+        let bid_end_time = duration.checked_add(Self::env().block_timestamp()).ok_or(MarketplaceError::ArithmeticOverflow)?;
+        self.list_bundle(items, 0, false, min_bid, bid_end_time)
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn buy_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError> {
+        let bundle = self.data::<Data>().bundles.get(&bundle_id).ok_or(MarketplaceError::BundleNotFound)?;
+        if bundle.direct == false {
+            return Err(MarketplaceError::TokenNotForDirectSale)
+        }
+        if Self::env().transferred_value() != bundle.price {
+            return Err(MarketplaceError::IneligibleBuyPrice)
+        }
+        let buyer = Self::env().caller();
+        if buyer == bundle.seller {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+
+        let price = bundle.price;
+        self.complete_bundle_sale(bundle_id, bundle, buyer, price)
+    }
+
+    default fn bid_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError> {
+        let mut bundle = self.data::<Data>().bundles.get(&bundle_id).ok_or(MarketplaceError::BundleNotFound)?;
+        if bundle.direct == true {
+            return Err(MarketplaceError::TokenOnlyForDirectSale)
+        }
+        if bundle.bid_end_time < Self::env().block_timestamp() {
+            return Err(MarketplaceError::AuctionExpired)
+        }
+        let bidder = Self::env().caller();
+        if bidder == bundle.seller {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        let value = Self::env().transferred_value();
+        let next_min_bid = match bundle.highest_bidder {
+            Some(_) => bundle.highest_bid
+                .saturating_add(self.data::<Data>().bid_inc_percent.saturating_mul(bundle.highest_bid) / 10000)
+                .saturating_add(1),
+            None => bundle.min_bid,
+        };
+        if value < next_min_bid {
+            return Err(MarketplaceError::MinimumBidNotMet)
+        }
+
+        if let Some(prev_bidder) = bundle.highest_bidder {
+            self.credit_account(prev_bidder, bundle.highest_bid);
+        }
+        bundle.highest_bid = value;
+        bundle.highest_bidder = Some(bidder);
+
+        self.data::<Data>().bundles.insert(&bundle_id, &bundle);
+        self.emit_bundle_bid_placed_event(bundle_id, bidder, value);
+
+        Ok(())
+    }
+
+    #[modifiers(non_reentrant)]
+    default fn settle_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError> {
+        let bundle = self.data::<Data>().bundles.get(&bundle_id).ok_or(MarketplaceError::BundleNotFound)?;
+        if bundle.direct == true {
+            return Err(MarketplaceError::TokenOnlyForDirectSale)
+        }
+        if bundle.bid_end_time >= Self::env().block_timestamp() {
+            return Err(MarketplaceError::AuctionOngoing)
+        }
+        let winner = bundle.highest_bidder.ok_or(MarketplaceError::NoValidBids)?;
+
+        let price = bundle.highest_bid;
+        self.complete_bundle_sale(bundle_id, bundle, winner, price)
+    }
+
+    default fn cancel_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError> {
+        let bundle = self.data::<Data>().bundles.get(&bundle_id).ok_or(MarketplaceError::BundleNotFound)?;
+        let seller = Self::env().caller();
+        if bundle.seller != seller {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        if bundle.highest_bidder.is_some() {
+            return Err(MarketplaceError::MinimumBidAlreadyMet)
+        }
+
+        for (address, token_id) in bundle.items {
+            self.transfer_token(address,seller,token_id.clone())
+                .map_err(|_| MarketplaceError::TransferToOwnerFailed)?;
+            self.set_auction_end(address, token_id)?;
+        }
+        self.data::<Data>().bundles.remove(&bundle_id);
+        self.emit_bundle_cancelled_event(bundle_id, seller);
+
+        Ok(())
+    }
+
+    default fn get_bundle(&self, bundle_id: u64) -> Option<Bundle> {
+        self.data::<Data>().bundles.get(&bundle_id)
+    }
+
+    default fn get_timestamp(&self) -> Timestamp {
+        return Self::env().block_timestamp()
+    } 
+
+    default fn get_blocknumber(&self) -> BlockNumber {
+        return Self::env().block_number()
+    } 
+}
+
+impl<T> MarketplaceEvents for T
+where
+    T: Storage<Data>,
+{
+    default fn emit_collection_added_event(&self, _collection: AccountId, _creator: AccountId, _royalty: u16) {}
+
+    default fn emit_item_registered_event(&self, _collection: AccountId, _token_id: Id, _owner: AccountId) {}
+
+    default fn emit_listed_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: AccountId, _price: Balance) {}
+
+    default fn emit_auction_created_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: AccountId, _buy_now: Option<Balance>, _min_bid: Balance, _end_time: Timestamp) {}
+
+    default fn emit_dutch_auction_created_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId, _start_price: Balance, _end_price: Balance, _start_time: Timestamp, _duration: Timestamp) {}
+
+    default fn emit_sealed_auction_created_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId, _min_bid: Balance, _commit_end_time: Timestamp, _reveal_end_time: Timestamp, _vickrey: bool) {}
+
+    default fn emit_bid_committed_event(&self, _collection: AccountId, _token_id: Id, _bidder: AccountId, _deposit: Balance) {}
+
+    default fn emit_bid_revealed_event(&self, _collection: AccountId, _token_id: Id, _bidder: AccountId, _amount: Balance) {}
+
+    default fn emit_bid_placed_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _bidder: AccountId, _amount: Balance, _next_min_bid: Balance, _end_time: Timestamp) {}
+
+    default fn emit_auction_extension_set_event(&self, _collection: AccountId, _token_id: Id, _extension_window: Timestamp, _extension_length: Timestamp, _max_end_time: Option<Timestamp>) {}
+
+    default fn emit_outbid_event(&self, _collection: AccountId, _token_id: Id, _bidder: AccountId, _refund: Balance) {}
+
+    default fn emit_sold_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: AccountId, _buyer: AccountId, _price: Balance,
+        _seller_share: Balance, _royalties: Balance, _market_fee: Balance, _creator: AccountId, _currency: Option<AccountId>) {}
+
+    default fn emit_auction_settled_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: Option<AccountId>, _winner: AccountId, _price: Balance) {}
+
+    default fn emit_listing_withdrawn_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: AccountId) {}
+
+    default fn emit_listing_expired_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId) {}
+
+    default fn emit_status_changed_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _status: ItemStatus) {}
+
+    default fn emit_collection_deactivated_event(&self, _collection: AccountId, _caller: AccountId) {}
+
+    default fn emit_royalty_split_set_event(&self, _collection: AccountId, _caller: AccountId, _split: Vec<(AccountId, u16)>) {}
+
+    default fn emit_collection_removed_event(&self, _collection: AccountId, _caller: AccountId) {}
+
+    default fn emit_item_removed_event(&self, _collection: AccountId, _token_id: Id, _owner: AccountId) {}
+
+    default fn emit_owner_synced_event(&self, _collection: AccountId, _token_id: Id, _owner: AccountId) {}
+
+    default fn emit_listing_invalidated_event(&self, _collection: AccountId, _token_id: Id, _listing_id: u64, _seller: Option<AccountId>) {}
+
+    default fn emit_keeper_rewarded_event(&self, _collection: AccountId, _token_id: Id, _keeper: AccountId, _amount: Balance, _currency: Option<AccountId>) {}
+
+    default fn emit_direct_sale_updated_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId, _price: Balance) {}
+
+    default fn emit_auction_updated_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId, _buy_now: Option<Balance>,
+        _min_bid: Balance, _end_time: Timestamp) {}
+
+    default fn emit_funds_withdrawn_event(&self, _account: AccountId, _amount: Balance) {}
+
+    default fn emit_token_funds_withdrawn_event(&self, _account: AccountId, _currency: AccountId, _amount: Balance) {}
+
+    default fn emit_bundle_created_event(&self, _bundle_id: u64, _seller: AccountId, _items: Vec<(AccountId, Id)>, _price: Balance, _min_bid: Balance, _end_time: Timestamp) {}
+
+    default fn emit_bundle_bid_placed_event(&self, _bundle_id: u64, _bidder: AccountId, _amount: Balance) {}
+
+    default fn emit_bundle_sold_event(&self, _bundle_id: u64, _seller: AccountId, _buyer: AccountId, _price: Balance) {}
+
+    default fn emit_bundle_cancelled_event(&self, _bundle_id: u64, _seller: AccountId) {}
+
+    default fn emit_offer_made_event(&self, _collection: AccountId, _token_id: Id, _offerer: AccountId, _amount: Balance, _expiry: Timestamp) {}
+
+    default fn emit_offer_cancelled_event(&self, _collection: AccountId, _token_id: Id, _offerer: AccountId, _amount: Balance) {}
+
+    default fn emit_offer_accepted_event(&self, _collection: AccountId, _token_id: Id, _seller: AccountId, _offerer: AccountId, _amount: Balance) {}
+
+    default fn emit_collection_offer_made_event(&self, _collection: AccountId, _offer_id: u64, _offerer: AccountId, _price: Balance, _quantity: u32, _expiry: Timestamp) {}
+
+    default fn emit_collection_offer_cancelled_event(&self, _collection: AccountId, _offer_id: u64, _offerer: AccountId, _refund: Balance) {}
+
+    default fn emit_collection_offer_filled_event(&self, _collection: AccountId, _offer_id: u64, _token_id: Id, _seller: AccountId, _offerer: AccountId, _price: Balance, _remaining: u32) {}
+}
+
+impl<T> Internal for T
+where
+    T: Storage<Data> + Storage<ownable::Data>,
+{
+    default fn calculate_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Result<Balance,MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address.clone(), token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let mut next_min_bid = item.highest_bid;
+        next_min_bid = next_min_bid.saturating_add(self.data::<Data>().bid_inc_percent.saturating_mul(next_min_bid) / 10000);
+        //item.next_min_bid = next_min_bid;
+        //self.data::<Data>().items.insert(&(address, token_id),&item);
+        Ok(next_min_bid)
+    }
+
+    default fn get_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Result<Balance,MarketplaceError> {
+        self.data::<Data>().items.get(&(address, token_id))
+            .map(|item| item.next_min_bid)
+            .ok_or(MarketplaceError::TokenDoesNotExist)
+    }
+
+    default fn get_ended_auction(&self, address: AccountId, token_id: Id) -> Result<AuctionItem,MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id)).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if item.seller != Some(Self::env().caller()) {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        if !item.status.is_auction() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        if item.bid_end_time >= Self::env().block_timestamp() {
+            return Err(MarketplaceError::AuctionOngoing)
+        }
+
+        Ok(item)
+    }
+
+    default fn return_unsold_auction(&mut self, address: AccountId, token_id: Id, item: AuctionItem, seller: AccountId) -> Result<(),MarketplaceError> {
+        if item.sealed.is_some() {
+            self.release_sealed_deposits(address, token_id.clone(), None, 0, seller);
+        } else if let Some(highest_bidder) = item.highest_bidder {
+            self.credit_currency(highest_bidder, item.currency, item.highest_bid);
+        }
+        self.transfer_token(address,seller,token_id.clone())
+            .map_err(|_| MarketplaceError::TransferToOwnerFailed)?;
+        self.set_auction_end(address.clone(),token_id.clone())?;
+        if let Some(highest_bidder) = item.highest_bidder {
+            self.emit_outbid_event(address, token_id.clone(), highest_bidder, item.highest_bid);
+        }
+        self.emit_listing_withdrawn_event(address, token_id, item.listing_id, seller);
+
         Ok(())
     }
 
-    default fn get_marketplace_fee(&self) -> u16 {
-        self.data::<Data>().fee
+    default fn reserve_grace_over(&self, item: &AuctionItem) -> bool {
+        item.bid_end_time.saturating_add(self.data::<Data>().reserve_grace_period) < Self::env().block_timestamp()
     }
 
-    default fn get_item_count(&self) -> u64 {
-        self.data::<Data>().item_count       
+    default fn list_bundle(&mut self, items: Vec<(AccountId, Id)>, price: Balance, direct: bool, min_bid: Balance, bid_end_time: Timestamp)
+    -> Result<u64,MarketplaceError> {
+        if items.len() < 2 || items.len() > MAX_BUNDLE_SIZE {
+            return Err(MarketplaceError::IneligibleBundleSize)
+        }
+        let seller = Self::env().caller();
+        let bundle_id = self.data::<Data>().bundle_count.saturating_add(1);
+
+        for (index, (address, token_id)) in items.iter().enumerate() {
+            if items[..index].contains(&(*address, token_id.clone())) {
+                return Err(MarketplaceError::DuplicateBundleItem)
+            }
+            let mut item = self.data::<Data>().items.get(&(*address, token_id.clone()))
+                .ok_or(MarketplaceError::TokenDoesNotExist)?;
+            self.check_transition(&item, ItemStatus::Bundled)?;
+            self.check_token_owner(*address, token_id.clone(), &mut item, seller)?;
+
+            self.take_custody(*address, token_id.clone())?;
+            self.transition(*address, token_id.clone(), &mut item, ItemStatus::Bundled)?;
+            item.bundle_id = Some(bundle_id);
+            self.data::<Data>().items.insert(&(*address, token_id.clone()), &item);
+        }
+
+        self.data::<Data>().bundles.insert(&bundle_id, &Bundle {
+            seller,
+            items: items.clone(),
+            price,
+            direct,
+            min_bid,
+            highest_bid: 0,
+            highest_bidder: None,
+            bid_end_time,
+        });
+        self.data::<Data>().bundle_count = bundle_id;
+        self.emit_bundle_created_event(bundle_id, seller, items, price, min_bid, bid_end_time);
+
+        Ok(bundle_id)
     }
 
-    default fn get_all_market_items(&self) -> Vec<(AccountId,Id)> {
-        self.data::<Data>().market_items.clone()
+    default fn complete_bundle_sale(&mut self, bundle_id: u64, bundle: Bundle, buyer: AccountId, price: Balance) -> Result<(),MarketplaceError> {
+        // Each token carries an equal share of the price, so every creator is paid
+        // royalties on their own part of the bundle. The last one takes the rounding dust.
+        let count = bundle.items.len() as u128;
+        let share = price / count;
+        let last = bundle.items.len() - 1;
+        for (index, (address, token_id)) in bundle.items.into_iter().enumerate() {
+            let item_price = if index == last { price - share * (count - 1) } else { share };
+            let breakdown = self.get_sales_breakdown(address, token_id.clone(), item_price)?;
+            self.transfer_token(address,buyer,token_id.clone())
+                .map_err(|_| MarketplaceError::TokenTransferFailed)?;
+            self.distribute_sale_proceeds(address, token_id.clone(), bundle.seller, buyer, item_price, breakdown, None)?;
+            self.set_auction_end(address, token_id)?;
+        }
+        self.data::<Data>().bundles.remove(&bundle_id);
+        self.emit_bundle_sold_event(bundle_id, bundle.seller, buyer, price);
+
+        Ok(())
     }
 
-    default fn get_timestamp(&self) -> Timestamp {
-        return Self::env().block_timestamp()
-    } 
+    default fn listing_expired(&self, item: &AuctionItem) -> bool {
+        item.status == ItemStatus::DirectSale && item.expiry.map_or(false, |expiry| expiry <= Self::env().block_timestamp())
+    }
 
-    default fn get_blocknumber(&self) -> BlockNumber {
-        return Self::env().block_number()
-    } 
-}
+    default fn get_open_auction(&self, address: AccountId, token_id: Id) -> Result<AuctionItem,MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        if item.status == ItemStatus::DirectSale {
+            return Err(MarketplaceError::TokenOnlyForDirectSale)
+        }
+        if item.sealed.is_some() {
+            return Err(MarketplaceError::SealedBidAuction)
+        }
+        self.check_listing_valid(address, token_id, &item)?;
 
-impl<T> Internal for T
-where
-    T: Storage<Data>,
-{
-    default fn calculate_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Balance {
-        let item = self.data::<Data>().items.get(&(address.clone(), token_id.clone())).unwrap();
-        let mut next_min_bid = item.highest_bid;
-        next_min_bid += self.data::<Data>().bid_inc_percent * next_min_bid / 10000;
-        //item.next_min_bid = next_min_bid;
-        //self.data::<Data>().items.insert(&(address, token_id),&item);
-        next_min_bid
+        Ok(item)
+    }
+
+    default fn place_bid(&mut self, address: AccountId, token_id: Id, item: AuctionItem, bidder: AccountId, value: Balance) -> Result<(),MarketplaceError> {
+        // A seller holding the highest bid could never settle, leaving the token stuck in escrow
+        if item.seller == Some(bidder) {
+            return Err(MarketplaceError::NotAuthorized)
+        }
+        if item.bid_end_time < Self::env().block_timestamp() {
+            return Err(MarketplaceError::AuctionExpired)
+        }
+        if value < item.next_min_bid {
+            return Err(MarketplaceError::MinimumBidNotMet)
+        }
+
+        if item.buy_now.map_or(false, |buy_now| value >= buy_now) {
+            let Ok((highest_bid,highest_bidder)) = self.update_highest_bid(address.clone(),token_id.clone(),bidder,value) else 
+            {return Err(MarketplaceError::BidNotUpdated)};
+            self.finalize_sale(address.clone(),token_id.clone(),bidder,value)?;
+
+            self.set_auction_end(address.clone(),token_id.clone())?;
+
+            Ok(())
+        } else {
+            let Ok((highest_bid,highest_bidder)) = self.update_highest_bid(address.clone(),token_id.clone(),bidder,value) else 
+            {return Err(MarketplaceError::BidNotUpdated)};
+            let next_min_bid = self.calculate_next_minimum_bid(address.clone(),token_id.clone())?;
+            let listing_id = item.listing_id;
+
+            // Bids close to the deadline push it back so the auction cannot be sniped
+            let now = Self::env().block_timestamp();
+            let mut bid_end_time = item.bid_end_time;
+            if item.extension_window > 0 && bid_end_time.saturating_sub(now) <= item.extension_window {
+                bid_end_time = core::cmp::max(bid_end_time, now.saturating_add(item.extension_length));
+                if let Some(max_end_time) = item.max_end_time {
+                    bid_end_time = core::cmp::min(bid_end_time, max_end_time);
+                }
+            }
+
+            self.data::<Data>().items.insert(&(address.clone(), token_id.clone()),
+            &AuctionItem{
+                highest_bid: highest_bid,
+                highest_bidder: highest_bidder,
+                next_min_bid: next_min_bid,
+                bid_end_time: bid_end_time,
+                ..item
+        });
+            self.emit_bid_placed_event(address, token_id, listing_id, bidder, value, next_min_bid, bid_end_time);
+            Ok(())
+        }
+    }
+
+    default fn check_currency(&self, currency: Option<AccountId>) -> Result<(),MarketplaceError> {
+        match currency {
+            Some(currency) if !self.data::<Data>().currencies.contains(&currency) => Err(MarketplaceError::CurrencyNotAccepted),
+            _ => Ok(()),
+        }
+    }
+
+    default fn receive_payment(&mut self, currency: AccountId, from: AccountId, amount: Balance) -> Result<(),MarketplaceError> {
+        let this = Self::env().account_id();
+        PSP22Ref::transfer_from(&currency, from, this, amount, ink::prelude::vec::Vec::new())
+            .map_err(|_| MarketplaceError::PaymentFailed)
+    }
+
+    default fn get_direct_sale_price(&self, item: &AuctionItem) -> Result<Balance,MarketplaceError> {
+        if self.listing_expired(item) {
+            return Err(MarketplaceError::ListingExpired)
+        }
+        match &item.dutch {
+            Some(dutch) => {
+                if Self::env().block_timestamp() < dutch.start_time {
+                    return Err(MarketplaceError::AuctionNotStarted)
+                }
+                Ok(self.calculate_dutch_price(dutch, Self::env().block_timestamp()))
+            },
+            None => Ok(item.buy_price),
+        }
+    }
+
+    default fn buy_item(&mut self, address: AccountId, token_id: Id, buyer: AccountId, available: Balance) -> Result<Balance,MarketplaceError> {
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if !item.status.is_on_sale() {
+            return Err(MarketplaceError::TokenNotForSale)
+        }
+        if item.status != ItemStatus::DirectSale {
+            return Err(MarketplaceError::TokenNotForDirectSale)
+        }
+        self.check_listing_valid(address, token_id.clone(), &item)?;
+        let price = self.get_direct_sale_price(&item)?;
+        // Batches can keep going past a failed item, so nothing may be paid for a sale that cannot complete
+        let (seller, breakdown) = self.check_sale(address, token_id.clone(), &item, buyer, price)?;
+        // Token priced listings are paid from the buyer's allowance, not from the transferred value
+        let spent = match item.currency {
+            Some(currency) => {
+                self.receive_payment(currency, buyer, price)?;
+                0
+            },
+            None => {
+                if available < price {
+                    return Err(MarketplaceError::IneligibleBuyPrice)
+                }
+                price
+            }
+        };
+
+        if let Err(error) = self.complete_sale(address.clone(),token_id.clone(),seller,buyer,price,breakdown,item.currency) {
+            // The token payment is already held by the marketplace, so the buyer can withdraw it
+            if item.currency.is_some() {
+                self.credit_currency(buyer, item.currency, price);
+            }
+            return Err(error)
+        }
+        self.set_auction_end(address,token_id)?;
+
+        Ok(spent)
+    }
+
+    default fn get_clearing_price(&self, item: &AuctionItem) -> Balance {
+        match &item.sealed {
+            // Vickrey winners pay the second highest bid, but never less than the minimum or the reserve
+            Some(sealed) if sealed.vickrey => {
+                let floor = core::cmp::max(item.min_bid, item.reserve_price);
+                core::cmp::min(item.highest_bid, core::cmp::max(sealed.second_highest_bid, floor))
+            },
+            _ => item.highest_bid,
+        }
+    }
+
+    default fn release_sealed_deposits(&mut self, address: AccountId, token_id: Id, winner: Option<AccountId>, price: Balance, seller: AccountId) {
+        let bidders = self.data::<Data>().sealed_bidders.get(&(address, token_id.clone())).unwrap_or_default();
+        let penalty = self.data::<Data>().items.get(&(address, token_id.clone()))
+            .and_then(|item| item.sealed)
+            .map_or(0, |sealed| u128::from(sealed.unrevealed_penalty));
+        for bidder in bidders {
+            let Some(bid) = self.data::<Data>().sealed_bids.get(&(address, token_id.clone(), bidder)) else { continue };
+            self.data::<Data>().sealed_bids.remove(&(address, token_id.clone(), bidder));
+
+            if winner == Some(bidder) {
+                self.credit_account(bidder, bid.deposit.saturating_sub(price));
+            } else if bid.revealed.is_some() {
+                self.credit_account(bidder, bid.deposit);
+            } else {
+                // Bidders that never revealed forfeit part of their deposit to the seller
+                let forfeit = bid.deposit.saturating_mul(penalty) / 10000;
+                self.credit_account(seller, forfeit);
+                self.credit_account(bidder, bid.deposit.saturating_sub(forfeit));
+            }
+        }
+        self.data::<Data>().sealed_bidders.remove(&(address, token_id));
     }
 
-    default fn get_next_minimum_bid(&self, address: AccountId, token_id: Id) -> Balance {
-        self.data::<Data>().items.get(&(address, token_id)).unwrap().next_min_bid
+    default fn calculate_dutch_price(&self, dutch: &DutchAuction, timestamp: Timestamp) -> Balance {
+        if timestamp <= dutch.start_time {
+            return dutch.start_price
+        }
+        let mut elapsed = core::cmp::min(timestamp.saturating_sub(dutch.start_time), dutch.duration);
+        // A stepped auction only drops its price once every `step` milliseconds
+        if dutch.step > 0 && elapsed < dutch.duration {
+            elapsed -= elapsed % dutch.step;
+        }
+        let decay = dutch.start_price.saturating_sub(dutch.end_price).saturating_mul(u128::from(elapsed)) / u128::from(dutch.duration);
+        dutch.start_price.saturating_sub(decay)
     }
 
-    default fn update_highest_bid(&self, address: AccountId, token_id: Id, new_bidder: AccountId, new_bid_amount: Balance)
+    default fn update_highest_bid(&mut self, address: AccountId, token_id: Id, new_bidder: AccountId, new_bid_amount: Balance)
     -> Result<(Balance,Option<AccountId>),MarketplaceError> {
-        let item = self.data::<Data>().items.get(&(address.clone(), token_id.clone())).unwrap();
+        let item = self.data::<Data>().items.get(&(address.clone(), token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
         let prev_bidder = item.highest_bidder;
         let prev_bid = item.highest_bid;
 
         match prev_bidder {
             Some(prev_bidder) => {
-                Self::env().transfer(prev_bidder,prev_bid).map_err(|_| MarketplaceError::TransferToBidderFailed)?;
+                // Refund is credited to the outbid bidder, who claims it with `withdraw_funds` or `withdraw_token_funds`
+                self.credit_currency(prev_bidder, item.currency, prev_bid);
+                self.emit_outbid_event(address, token_id, prev_bidder, prev_bid);
                 let highest_bid = new_bid_amount;
                 let highest_bidder = Some(new_bidder);
                 //self.data::<Data>().items.insert(&(address, token_id),&item);
@@ -474,57 +2085,403 @@ where
         self.data::<Data>().collections.get(&address).is_some()
     }
 
-    default fn finalize_sale(&self, address: AccountId, token_id: Id, sales_price: Balance) -> Result<(),MarketplaceError> {
-        let owner = PSP34Ref::owner_of(&address, token_id.clone()).ok_or(MarketplaceError::TokenDoesNotExist)?;
-        let buyer = Self::env().caller();
+    default fn check_collection_active(&self, address: AccountId) -> Result<(),MarketplaceError> {
+        let collection = self.data::<Data>().collections.get(&address)
+            .ok_or(MarketplaceError::CollectionNotRegisteredToMarketplace)?;
+        if !collection.active {
+            return Err(MarketplaceError::CollectionInactive)
+        }
+
+        Ok(())
+    }
+
+    default fn get_managed_collection(&self, address: AccountId) -> Result<Collection,MarketplaceError> {
+        let collection = self.data::<Data>().collections.get(&address)
+            .ok_or(MarketplaceError::CollectionNotRegisteredToMarketplace)?;
+        let caller = Self::env().caller();
+        if collection.creator != Some(caller) && self.data::<ownable::Data>().owner != caller {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+
+        Ok(collection)
+    }
+
+    default fn finalize_sale(&mut self, address: AccountId, token_id: Id, buyer: AccountId, sales_price: Balance) -> Result<Balance,MarketplaceError> {
+        // The token is escrowed by the marketplace, so the seller recorded on the listing gets paid
+        let item = self.data::<Data>().items.get(&(address, token_id.clone())).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        let (owner, breakdown) = self.check_sale(address, token_id.clone(), &item, buyer, sales_price)?;
+        self.complete_sale(address, token_id, owner, buyer, sales_price, breakdown, item.currency)?;
+
+        Ok(breakdown.2)
+    }
+
+    default fn check_sale(&self, address: AccountId, token_id: Id, item: &AuctionItem, buyer: AccountId, sales_price: Balance)
+    -> Result<(AccountId, (Balance, Balance, Balance, AccountId)),MarketplaceError> {
+        let owner = item.seller.ok_or(MarketplaceError::TokenNotForSale)?;
         if buyer == owner {
             return Err(MarketplaceError::NotAuthorized)
         }
-        let (seller_share,royalties,market_fees,creator) = self.get_sales_breakdown(address.clone(),token_id.clone(),sales_price);
-
-        match PSP34Ref::transfer(&address,buyer,token_id.clone(),ink::prelude::vec::Vec::new()) {
-            Ok(()) => {
-                Self::env().transfer(owner, seller_share)
-                    .map_err(|_| MarketplaceError::TransferToOwnerFailed)?;
-                Self::env().transfer(self.data::<Data>().market_fee_recipient, market_fees)
-                    .map_err(|_| MarketplaceError::MarketplaceFeeTransferFailed)?;
-                Self::env().transfer(creator, royalties)
-                    .map_err(|_| MarketplaceError::RoyaltiesTransferFailed)?;
-                Ok(())
-            },
-            Err(_) => return Err(MarketplaceError::TransferToContractFailed)
-        }
+        let breakdown = self.get_sales_breakdown(address, token_id, sales_price)?;
+
+        Ok((owner, breakdown))
+    }
+
+    default fn complete_sale(&mut self, address: AccountId, token_id: Id, seller: AccountId, buyer: AccountId, sales_price: Balance,
+        breakdown: (Balance, Balance, Balance, AccountId), currency: Option<AccountId>) -> Result<(),MarketplaceError> {
+        self.transfer_token(address,buyer,token_id.clone())
+            .map_err(|_| MarketplaceError::TransferToContractFailed)?;
+        self.distribute_sale_proceeds(address, token_id, seller, buyer, sales_price, breakdown, currency)
+    }
+
+    default fn distribute_sale_proceeds(&mut self, address: AccountId, token_id: Id, seller: AccountId, buyer: AccountId, sales_price: Balance,
+        breakdown: (Balance, Balance, Balance, AccountId), currency: Option<AccountId>) -> Result<(),MarketplaceError> {
+        let (seller_share,royalties,market_fees,creator) = breakdown;
+        let listing_id = self.data::<Data>().items.get(&(address, token_id.clone()))
+            .filter(|item| item.status.is_on_sale())
+            .map_or(0, |item| item.listing_id);
+        let market_fee_recipient = self.data::<Data>().market_fee_recipient;
+        self.credit_currency(seller, currency, seller_share);
+        self.credit_currency(market_fee_recipient, currency, market_fees);
+        self.pay_royalties(address, creator, currency, royalties);
+        self.emit_sold_event(address, token_id, listing_id, seller, buyer, sales_price, seller_share, royalties, market_fees, creator, currency);
+
+        Ok(())
     }
 
     default fn get_sales_breakdown(&self, address: AccountId, token_id: Id, sales_price: Balance) 
-    -> (Balance, Balance, Balance, AccountId) {
-        let market_fees: Balance = u128::from(self.data::<Data>().fee) * sales_price / 10000;
-        let collection = self.data::<Data>().collections.get(&address).unwrap();
-        let creator = collection.creator.unwrap();
-        let royalties = u128::from(collection.royalty) * sales_price /10000;
-        //let Ok((royalties,creator)) = TokenRef::get_royalty_info(&address,token_id,sales_price);
-        let seller_share = sales_price - market_fees - royalties;
+    -> Result<(Balance, Balance, Balance, AccountId),MarketplaceError> {
+        let market_fees: Balance = u128::from(self.data::<Data>().fee).checked_mul(sales_price)
+            .ok_or(MarketplaceError::ArithmeticOverflow)? / 10000;
+        let collection = self.data::<Data>().collections.get(&address).ok_or(MarketplaceError::CollectionNotRegisteredToMarketplace)?;
+        let (creator, royalties) = match self.get_royalty_info(address, token_id, sales_price) {
+            Some(info) => info,
+            None => {
+                let creator = collection.creator.ok_or(MarketplaceError::CreatorNotFound)?;
+                let royalties = u128::from(collection.royalty).checked_mul(sales_price)
+                    .ok_or(MarketplaceError::ArithmeticOverflow)? / 10000;
+                (creator, royalties)
+            }
+        };
+        let max_royalties = u128::from(self.data::<Data>().max_royalty).checked_mul(sales_price)
+            .ok_or(MarketplaceError::ArithmeticOverflow)? / 10000;
+        let royalties = core::cmp::min(royalties, max_royalties);
+        let seller_share = sales_price.checked_sub(market_fees)
+            .and_then(|share| share.checked_sub(royalties))
+            .ok_or(MarketplaceError::ArithmeticOverflow)?;
         
-        (seller_share,royalties,market_fees,creator)
+        Ok((seller_share,royalties,market_fees,creator))
     }
 
     default fn set_auction_end(&mut self, address: AccountId, token_id: Id) -> Result<(),MarketplaceError> {
-        let token_owner = PSP34Ref::owner_of(&address.clone(), token_id.clone())
+        let token_owner = self.get_token_owner(address, token_id.clone())
             .ok_or(MarketplaceError::TokenDoesNotExist)?;
+        self.release_custody(address.clone(), token_id.clone());
+        // Items sold off-market through offers were never listed
+        if let Some(mut item) = self.data::<Data>().items.get(&(address, token_id.clone())) {
+            if item.status != ItemStatus::Unlisted {
+                self.transition(address, token_id.clone(), &mut item, ItemStatus::Unlisted)?;
+            }
+        }
         self.data::<Data>().items.insert(&(address, token_id),
         &AuctionItem{
             owner: token_owner,
-            buy_price: 0,
-            seller: None,
-            highest_bid: 0,
-            highest_bidder: None,
-            min_bid: 0,
-            next_min_bid: 0,
-            bid_end_time: 0,
-            on_sale: false,
-            direct: false,
+            ..Default::default()
         });
 
         Ok(())
     }
-}
\ No newline at end of file
+
+    default fn check_transition(&self, item: &AuctionItem, status: ItemStatus) -> Result<(),MarketplaceError> {
+        match (item.status, status) {
+            (_, ItemStatus::AwaitingSettlement) => Err(MarketplaceError::InvalidStatusTransition),
+            (ItemStatus::Unlisted, ItemStatus::Unlisted) => Err(MarketplaceError::TokenNotForSale),
+            (ItemStatus::Unlisted, _) => Ok(()),
+            (_, ItemStatus::Unlisted) => Ok(()),
+            _ => Err(MarketplaceError::TokenAlreadyOnSale),
+        }
+    }
+
+    default fn transition(&mut self, address: AccountId, token_id: Id, item: &mut AuctionItem, status: ItemStatus) -> Result<(),MarketplaceError> {
+        self.check_transition(item, status)?;
+        // Every new listing gets its own id, so events can tell relistings of the same token apart
+        if item.status == ItemStatus::Unlisted {
+            let listing_id = self.data::<Data>().listing_count.saturating_add(1);
+            self.data::<Data>().listing_count = listing_id;
+            item.listing_id = listing_id;
+        }
+        item.status = status;
+        self.emit_status_changed_event(address, token_id, item.listing_id, status);
+
+        Ok(())
+    }
+
+    default fn get_status(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> ItemStatus {
+        if item.status.is_on_sale() && !self.listing_valid(address, token_id, item) {
+            return ItemStatus::Invalid
+        }
+        if item.status.is_auction() && item.bid_end_time < Self::env().block_timestamp() {
+            return ItemStatus::AwaitingSettlement
+        }
+        item.status
+    }
+
+    default fn is_settleable(&self, item: &AuctionItem) -> bool {
+        item.status.is_auction()
+            && item.bid_end_time < Self::env().block_timestamp()
+            && (item.highest_bidder.is_none()
+                || item.highest_bid >= item.reserve_price
+                || self.reserve_grace_over(item))
+    }
+
+    default fn get_token_owner(&self, address: AccountId, token_id: Id) -> Option<AccountId> {
+        PSP34Ref::owner_of(&address, token_id)
+    }
+
+    default fn transfer_token(&mut self, address: AccountId, to: AccountId, token_id: Id) -> Result<(),PSP34Error> {
+        PSP34Ref::transfer(&address, to, token_id, ink::prelude::vec::Vec::new())
+    }
+
+    default fn get_royalty_info(&self, address: AccountId, token_id: Id, sales_price: Balance) -> Option<(AccountId, Balance)> {
+        // Collections without the royalty interface make the call fail, which is not an error here.
+        // The gas limit keeps a hostile collection from burning the buyer's gas.
+        match TokenRoyaltyRef::royalty_info_builder(&address, token_id, sales_price)
+            .gas_limit(ROYALTY_QUERY_GAS)
+            .fire() {
+            Ok(Ok(info)) => Some(info),
+            _ => None,
+        }
+    }
+
+    default fn is_approved(&self, address: AccountId, token_id: Id, owner: AccountId) -> bool {
+        let this = Self::env().account_id();
+        PSP34Ref::allowance(&address, owner, this, Some(token_id)) || PSP34Ref::allowance(&address, owner, this, None)
+    }
+
+    default fn check_token_owner(&self, address: AccountId, token_id: Id, item: &mut AuctionItem, account: AccountId) -> Result<(),MarketplaceError> {
+        let owner = self.get_token_owner(address, token_id).ok_or(MarketplaceError::TokenDoesNotExist)?;
+        if owner != account {
+            return Err(MarketplaceError::NotTheOwner)
+        }
+        item.owner = owner;
+
+        Ok(())
+    }
+
+    default fn listing_valid(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> bool {
+        // Escrowed tokens must still be held by the marketplace, anything else by the seller
+        let holder = if self.index_contains(ItemIndex::Escrowed, address, token_id.clone()) {
+            Self::env().account_id()
+        } else {
+            match item.seller {
+                Some(seller) => seller,
+                None => return false,
+            }
+        };
+        self.get_token_owner(address, token_id) == Some(holder)
+    }
+
+    default fn check_listing_valid(&self, address: AccountId, token_id: Id, item: &AuctionItem) -> Result<(),MarketplaceError> {
+        if !self.listing_valid(address, token_id, item) {
+            return Err(MarketplaceError::ListingInvalid)
+        }
+
+        Ok(())
+    }
+
+    default fn invalidate_listing(&mut self, address: AccountId, token_id: Id, item: AuctionItem) -> Result<(),MarketplaceError> {
+        // Nobody is at fault for a sealed auction that cannot complete, so every deposit is returned in full
+        for bidder in self.data::<Data>().sealed_bidders.get(&(address, token_id.clone())).unwrap_or_default() {
+            if let Some(bid) = self.data::<Data>().sealed_bids.get(&(address, token_id.clone(), bidder)) {
+                self.data::<Data>().sealed_bids.remove(&(address, token_id.clone(), bidder));
+                self.credit_account(bidder, bid.deposit);
+            }
+        }
+        self.data::<Data>().sealed_bidders.remove(&(address, token_id.clone()));
+        if item.sealed.is_none() {
+            if let Some(highest_bidder) = item.highest_bidder {
+                self.credit_currency(highest_bidder, item.currency, item.highest_bid);
+            }
+        }
+
+        self.set_auction_end(address, token_id.clone())?;
+        self.emit_listing_invalidated_event(address, token_id, item.listing_id, item.seller);
+
+        Ok(())
+    }
+
+    default fn credit_account(&mut self, account: AccountId, amount: Balance) {
+        if amount == 0 {
+            return
+        }
+        let pending = self.data::<Data>().pending_withdrawals.get(&account).unwrap_or(0);
+        self.data::<Data>().pending_withdrawals.insert(&account, &pending.saturating_add(amount));
+        let total = self.data::<Data>().total_pending_withdrawals.saturating_add(amount);
+        self.data::<Data>().total_pending_withdrawals = total;
+    }
+
+    default fn credit_currency(&mut self, account: AccountId, currency: Option<AccountId>, amount: Balance) {
+        let Some(currency) = currency else {
+            return self.credit_account(account, amount)
+        };
+        if amount == 0 {
+            return
+        }
+        let pending = self.data::<Data>().pending_token_withdrawals.get(&(account, currency)).unwrap_or(0);
+        self.data::<Data>().pending_token_withdrawals.insert(&(account, currency), &pending.saturating_add(amount));
+        let liabilities = self.data::<Data>().token_liabilities.get(&currency).unwrap_or(0);
+        self.data::<Data>().token_liabilities.insert(&currency, &liabilities.saturating_add(amount));
+    }
+
+    default fn pay_royalties(&mut self, address: AccountId, receiver: AccountId, currency: Option<AccountId>, royalties: Balance) {
+        // A receiver reported by the collection's own `royalty_info` is paid as reported
+        let creator = self.data::<Data>().collections.get(&address).and_then(|collection| collection.creator);
+        let split = if creator == Some(receiver) {
+            self.data::<Data>().royalty_splits.get(&address).unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        let mut paid: Balance = 0;
+        for (recipient, share) in split {
+            let amount = royalties.saturating_mul(u128::from(share)) / 10000;
+            self.credit_currency(recipient, currency, amount);
+            paid = paid.saturating_add(amount);
+        }
+        self.credit_currency(receiver, currency, royalties.saturating_sub(paid));
+    }
+
+    default fn debit_currency(&mut self, account: AccountId, currency: Option<AccountId>, amount: Balance) {
+        if amount == 0 {
+            return
+        }
+        match currency {
+            Some(currency) => {
+                let pending = self.data::<Data>().pending_token_withdrawals.get(&(account, currency)).unwrap_or(0);
+                self.data::<Data>().pending_token_withdrawals.insert(&(account, currency), &pending.saturating_sub(amount));
+                let liabilities = self.data::<Data>().token_liabilities.get(&currency).unwrap_or(0);
+                self.data::<Data>().token_liabilities.insert(&currency, &liabilities.saturating_sub(amount));
+            },
+            None => {
+                let pending = self.data::<Data>().pending_withdrawals.get(&account).unwrap_or(0);
+                self.data::<Data>().pending_withdrawals.insert(&account, &pending.saturating_sub(amount));
+                let total = self.data::<Data>().total_pending_withdrawals.saturating_sub(amount);
+                self.data::<Data>().total_pending_withdrawals = total;
+            }
+        }
+    }
+
+    default fn pay_keeper_reward(&mut self, address: AccountId, token_id: Id, keeper: AccountId, market_fees: Balance, currency: Option<AccountId>)
+    -> Result<Balance,MarketplaceError> {
+        // The reward comes out of the market fee that was just credited to the fee recipient
+        let rate = self.data::<Data>().keeper_reward;
+        let market_fee_recipient = self.data::<Data>().market_fee_recipient;
+        if rate == 0 || keeper == market_fee_recipient {
+            return Ok(0)
+        }
+        let reward = market_fees.saturating_mul(u128::from(rate)) / 10000;
+        if reward == 0 {
+            return Ok(0)
+        }
+        self.debit_currency(market_fee_recipient, currency, reward);
+        self.credit_currency(keeper, currency, reward);
+        self.emit_keeper_rewarded_event(address, token_id, keeper, reward, currency);
+
+        Ok(reward)
+    }
+
+    default fn remove_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<Offer,MarketplaceError> {
+        let offer = self.data::<Data>().offers.get(&(address, token_id.clone(), offerer))
+            .ok_or(MarketplaceError::OfferNotFound)?;
+        self.data::<Data>().offers.remove(&(address, token_id.clone(), offerer));
+
+        let mut offerers = self.data::<Data>().offerers.get(&(address, token_id.clone())).unwrap_or_default();
+        offerers.retain(|account| *account != offerer);
+        if offerers.is_empty() {
+            self.data::<Data>().offerers.remove(&(address, token_id));
+        } else {
+            self.data::<Data>().offerers.insert(&(address, token_id), &offerers);
+        }
+
+        Ok(offer)
+    }
+
+    default fn refund_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<(),MarketplaceError> {
+        let offer = self.remove_offer(address, token_id.clone(), offerer)?;
+        self.credit_account(offerer, offer.amount);
+        self.emit_offer_cancelled_event(address, token_id, offerer, offer.amount);
+
+        Ok(())
+    }
+
+    default fn refund_collection_offer(&mut self, address: AccountId, offer: CollectionOffer) -> Result<(),MarketplaceError> {
+        let refund = offer.price.checked_mul(u128::from(offer.quantity)).ok_or(MarketplaceError::ArithmeticOverflow)?;
+        self.credit_account(offer.offerer, refund);
+        self.emit_collection_offer_cancelled_event(address, offer.id, offer.offerer, refund);
+
+        Ok(())
+    }
+
+    default fn take_custody(&mut self, address: AccountId, token_id: Id) -> Result<(),MarketplaceError> {
+        // Listings already running in a deactivated collection can still complete or be withdrawn
+        self.check_collection_active(address)?;
+        if !self.is_approved(address, token_id.clone(), Self::env().caller()) {
+            return Err(MarketplaceError::ApprovalFailed)
+        }
+        let this = Self::env().account_id();
+        self.transfer_token(address,this,token_id.clone())
+            .map_err(|_| MarketplaceError::TransferToContractFailed)?;
+        // Every listing path escrows the token, so this is also where it enters the listing indexes
+        self.index_insert(ItemIndex::Escrowed, address, token_id.clone());
+        self.index_insert(ItemIndex::Active, address, token_id.clone());
+        self.index_insert(ItemIndex::Seller(Self::env().caller()), address, token_id);
+
+        Ok(())
+    }
+
+    default fn release_custody(&mut self, address: AccountId, token_id: Id) {
+        // The item still records the listing owner until `set_auction_end` resets it
+        if let Some(item) = self.data::<Data>().items.get(&(address, token_id.clone())) {
+            self.index_remove(ItemIndex::Seller(item.owner), address, token_id.clone());
+        }
+        self.index_remove(ItemIndex::Active, address, token_id.clone());
+        self.index_remove(ItemIndex::Escrowed, address, token_id);
+    }
+
+    default fn index_contains(&self, index: ItemIndex, address: AccountId, token_id: Id) -> bool {
+        self.data::<Data>().index_positions.get(&(index, address, token_id)).is_some()
+    }
+
+    default fn index_insert(&mut self, index: ItemIndex, address: AccountId, token_id: Id) {
+        if self.index_contains(index, address, token_id.clone()) {
+            return
+        }
+        let length = self.data::<Data>().index_lengths.get(&index).unwrap_or(0);
+        self.data::<Data>().index_entries.insert(&(index, length), &(address, token_id.clone()));
+        self.data::<Data>().index_positions.insert(&(index, address, token_id), &length);
+        self.data::<Data>().index_lengths.insert(&index, &(length + 1));
+    }
+
+    default fn index_remove(&mut self, index: ItemIndex, address: AccountId, token_id: Id) {
+        let Some(position) = self.data::<Data>().index_positions.get(&(index, address, token_id.clone())) else {
+            return
+        };
+        // Move the last entry into the freed slot so the index stays contiguous
+        let last = self.data::<Data>().index_lengths.get(&index).unwrap_or(1) - 1;
+        if position != last {
+            if let Some(moved) = self.data::<Data>().index_entries.get(&(index, last)) {
+                self.data::<Data>().index_entries.insert(&(index, position), &moved);
+                self.data::<Data>().index_positions.insert(&(index, moved.0, moved.1), &position);
+            }
+        }
+        self.data::<Data>().index_entries.remove(&(index, last));
+        self.data::<Data>().index_positions.remove(&(index, address, token_id));
+        self.data::<Data>().index_lengths.insert(&index, &last);
+    }
+
+    default fn index_page(&self, index: ItemIndex, offset: u64, limit: u64) -> Vec<(AccountId,Id)> {
+        let length = self.data::<Data>().index_lengths.get(&index).unwrap_or(0);
+        let end = core::cmp::min(length, offset.saturating_add(core::cmp::min(limit, MAX_PAGE_SIZE)));
+        (offset..end)
+            .filter_map(|position| self.data::<Data>().index_entries.get(&(index, position)))
+            .collect()
+    }
+}
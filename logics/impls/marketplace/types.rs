@@ -22,6 +22,17 @@ use scale::{
 
 pub const STORAGE_KEY: u32 = openbrush::storage_unique_key!(Data);
 
+pub const MAX_OFFERS_PER_TOKEN: usize = 50;
+pub const MAX_COLLECTION_OFFERS: usize = 100;
+pub const MAX_SEALED_BIDS: usize = 100;
+pub const MAX_BATCH_SIZE: usize = 100;
+pub const MAX_BUNDLE_SIZE: usize = 20;
+pub const MAX_PAGE_SIZE: u64 = 100;
+pub const MAX_ROYALTY_RECIPIENTS: usize = 10;
+pub const MAX_COLLECTIONS_PER_CREATOR: usize = 100;
+/// Gas a collection's `royalty_info` may use before the marketplace falls back to the registered royalty.
+pub const ROYALTY_QUERY_GAS: u64 = 5_000_000_000;
+
 #[derive(Debug)]
 #[openbrush::upgradeable_storage(STORAGE_KEY)]
 pub struct Data {
@@ -33,7 +44,30 @@ pub struct Data {
     pub contract_hash: Hash,
     pub collection_count: u64,
     pub item_count: u64,
-    pub market_items: Vec<(AccountId,Id)>,
+    pub index_entries: Mapping<(ItemIndex, u64), (AccountId, Id)>,
+    pub index_lengths: Mapping<ItemIndex, u64>,
+    pub index_positions: Mapping<(ItemIndex, AccountId, Id), u64>,
+    pub pending_withdrawals: Mapping<AccountId, Balance>,
+    pub total_pending_withdrawals: Balance,
+    pub offers: Mapping<(AccountId, Id, AccountId), Offer>,
+    pub offerers: Mapping<(AccountId, Id), Vec<AccountId>>,
+    pub collection_offers: Mapping<AccountId, Vec<CollectionOffer>>,
+    pub collection_offer_count: u64,
+    pub sealed_bids: Mapping<(AccountId, Id, AccountId), SealedBid>,
+    pub sealed_bidders: Mapping<(AccountId, Id), Vec<AccountId>>,
+    pub unrevealed_penalty: u16,
+    pub bundles: Mapping<u64, Bundle>,
+    pub bundle_count: u64,
+    pub listing_count: u64,
+    pub currencies: Vec<AccountId>,
+    pub pending_token_withdrawals: Mapping<(AccountId, AccountId), Balance>,
+    pub token_liabilities: Mapping<AccountId, Balance>,
+    pub keeper_reward: u16,
+    pub max_royalty: u16,
+    pub royalty_splits: Mapping<AccountId, Vec<(AccountId, u16)>>,
+    pub creator_collections: Mapping<AccountId, Vec<AccountId>>,
+    pub collection_nonce: u64,
+    pub reserve_grace_period: Timestamp,
 }
 
 impl Default for Data {
@@ -47,7 +81,30 @@ impl Default for Data {
             contract_hash: Default::default(),
             collection_count: 0,
             item_count: 0,
-            market_items: Default::default(),
+            index_entries: Default::default(),
+            index_lengths: Default::default(),
+            index_positions: Default::default(),
+            pending_withdrawals: Default::default(),
+            total_pending_withdrawals: 0,
+            offers: Default::default(),
+            offerers: Default::default(),
+            collection_offers: Default::default(),
+            collection_offer_count: 0,
+            sealed_bids: Default::default(),
+            sealed_bidders: Default::default(),
+            unrevealed_penalty: 0,
+            bundles: Default::default(),
+            bundle_count: 0,
+            listing_count: 0,
+            currencies: Default::default(),
+            pending_token_withdrawals: Default::default(),
+            token_liabilities: Default::default(),
+            keeper_reward: 0,
+            max_royalty: 0,
+            royalty_splits: Default::default(),
+            creator_collections: Default::default(),
+            collection_nonce: 0,
+            reserve_grace_period: 0,
         }
     }
 }
@@ -113,6 +170,154 @@ pub enum MarketplaceError {
     ApprovalFailed,
     // Bid Not Update
     BidNotUpdated,
+    // No Funds To Withdraw
+    NoFundsToWithdraw,
+    // Withdrawal Failed
+    WithdrawalFailed,
+    // Ineligible Offer Amount
+    IneligibleOfferAmount,
+    // Ineligible Offer Expiry
+    IneligibleOfferExpiry,
+    // Offer Already Exists
+    OfferAlreadyExists,
+    // Offer Not Found
+    OfferNotFound,
+    // Offer Expired
+    OfferExpired,
+    // Too Many Offers
+    TooManyOffers,
+    // Token Transfer Failed
+    TokenTransferFailed,
+    // Auction Not Started
+    AuctionNotStarted,
+    // Refund Failed
+    RefundFailed,
+    // Reserve Not Met
+    ReserveNotMet,
+    // Reserve Already Met
+    ReserveAlreadyMet,
+    // Sealed Bid Auction
+    SealedBidAuction,
+    // Not Sealed Bid Auction
+    NotSealedBidAuction,
+    // Commit Phase Over
+    CommitPhaseOver,
+    // Reveal Phase Not Started
+    RevealPhaseNotStarted,
+    // Bid Already Committed
+    BidAlreadyCommitted,
+    // Bid Already Revealed
+    BidAlreadyRevealed,
+    // Bid Not Found
+    BidNotFound,
+    // Invalid Reveal
+    InvalidReveal,
+    // Too Many Bids
+    TooManyBids,
+    // Ineligible Bid Limit
+    IneligibleBidLimit,
+    // Ineligible Penalty
+    IneligiblePenalty,
+    // Batch Too Large
+    BatchTooLarge,
+    // Bundle Not Found
+    BundleNotFound,
+    // Ineligible Bundle Size
+    IneligibleBundleSize,
+    // Duplicate Bundle Item
+    DuplicateBundleItem,
+    // Currency Not Accepted
+    CurrencyNotAccepted,
+    // Currency Already Accepted
+    CurrencyAlreadyAccepted,
+    // Currency Mismatch
+    CurrencyMismatch,
+    // Payment Failed
+    PaymentFailed,
+    // Ineligible Listing Expiry
+    IneligibleListingExpiry,
+    // Listing Expired
+    ListingExpired,
+    // Listing Not Expired
+    ListingNotExpired,
+    // Price Not Editable
+    PriceNotEditable,
+    // Auction Has Bids
+    AuctionHasBids,
+    // Invalid Status Transition
+    InvalidStatusTransition,
+    // Collection Inactive
+    CollectionInactive,
+    // Collection Has Items
+    CollectionHasItems,
+    // Listing Invalid
+    ListingInvalid,
+    // Arithmetic Overflow
+    ArithmeticOverflow,
+    // Fee Too High
+    FeeTooHigh,
+    // Ineligible Keeper Reward
+    IneligibleKeeperReward,
+    // Royalty Too High
+    RoyaltyTooHigh,
+    // Invalid Royalty Split
+    InvalidRoyaltySplit,
+    // Too Many Royalty Recipients
+    TooManyRoyaltyRecipients,
+    // Too Many Collections
+    TooManyCollections,
+    // Creator Not Found
+    CreatorNotFound,
+}
+
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum ItemStatus {
+    // Registered but not on sale
+    Unlisted,
+    // Fixed price or Dutch auction sale
+    DirectSale,
+    // Open bid auction
+    EnglishAuction,
+    // Commit-reveal auction
+    SealedAuction,
+    // Part of a bundle
+    Bundled,
+    // Auction past its end time. Only reported by queries, never stored
+    AwaitingSettlement,
+    // Listing whose token left the marketplace or seller. Only reported by queries, never stored
+    Invalid,
+}
+
+impl ItemStatus {
+    pub fn is_on_sale(&self) -> bool {
+        matches!(self, ItemStatus::DirectSale | ItemStatus::EnglishAuction | ItemStatus::SealedAuction)
+    }
+
+    pub fn is_auction(&self) -> bool {
+        matches!(self, ItemStatus::EnglishAuction | ItemStatus::SealedAuction)
+    }
+}
+
+/// Whether a listing can still be filled, as reported by `listing_health`.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "std", derive(scale_info::TypeInfo))]
+pub enum ListingHealth {
+    // Can be bought or bid on
+    Healthy,
+    // Not on sale as a single item
+    NotListed,
+    // Token no longer held by the marketplace or seller
+    TokenMoved,
+    // Seller revoked the marketplace's approval
+    ApprovalMissing,
+    // Direct sale past its expiry
+    Expired,
+    // Bidding is over and the auction waits for settlement
+    AuctionEnded,
 }
 
 #[derive(Encode, Decode, Debug)]
@@ -123,14 +328,29 @@ pub enum MarketplaceError {
 pub struct AuctionItem {
     pub owner: AccountId,
     pub buy_price: Balance,
+    pub buy_now: Option<Balance>,
     pub seller: Option<AccountId>,
     pub highest_bid: Balance,
     pub highest_bidder: Option<AccountId>,
     pub min_bid: Balance,
     pub next_min_bid: Balance,
+    /// Lowest winning bid. Masked by `get_item` when hidden, but still readable from raw storage.
+    pub reserve_price: Balance,
+    pub reserve_hidden: bool,
     pub bid_end_time: Timestamp,
-    pub on_sale: bool,
-    pub direct: bool,
+    pub status: ItemStatus,
+    /// Id of the current listing, zero while unlisted.
+    pub listing_id: u64,
+    pub dutch: Option<DutchAuction>,
+    pub extension_window: Timestamp,
+    pub extension_length: Timestamp,
+    pub max_end_time: Option<Timestamp>,
+    pub sealed: Option<SealedAuction>,
+    pub bundle_id: Option<u64>,
+    /// PSP22 token the listing is priced in, or `None` for the native currency.
+    pub currency: Option<AccountId>,
+    /// Direct sales can no longer be bought after this time.
+    pub expiry: Option<Timestamp>,
 }
 
 impl Default for AuctionItem {
@@ -138,18 +358,129 @@ impl Default for AuctionItem {
         Self {
             owner: ZERO_ADDRESS.into(),
             buy_price: 0,
+            buy_now: None,
             seller: None,
             highest_bid: 0,
             highest_bidder: None,
             min_bid: 0,
             next_min_bid: 0,
+            reserve_price: 0,
+            reserve_hidden: false,
             bid_end_time: 0,
-            on_sale: false,
-            direct: false,
+            status: ItemStatus::Unlisted,
+            listing_id: 0,
+            dutch: None,
+            extension_window: 0,
+            extension_length: 0,
+            max_end_time: None,
+            sealed: None,
+            bundle_id: None,
+            currency: None,
+            expiry: None,
         }
     }
 }
 
+/// Several tokens sold together, either at a fixed `price` or by auction when `direct` is false.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Bundle {
+    pub seller: AccountId,
+    pub items: Vec<(AccountId, Id)>,
+    pub price: Balance,
+    pub direct: bool,
+    pub min_bid: Balance,
+    pub highest_bid: Balance,
+    pub highest_bidder: Option<AccountId>,
+    pub bid_end_time: Timestamp,
+}
+
+/// Price schedule of a descending-price listing.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct DutchAuction {
+    pub start_price: Balance,
+    pub end_price: Balance,
+    pub start_time: Timestamp,
+    pub duration: Timestamp,
+    pub step: Timestamp,
+}
+
+/// Commit-reveal schedule of a sealed-bid auction. The reveal phase ends at `bid_end_time`.
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct SealedAuction {
+    pub commit_end_time: Timestamp,
+    pub vickrey: bool,
+    pub second_highest_bid: Balance,
+    /// Most bids the auction takes, set by the seller up to `MAX_SEALED_BIDS`.
+    pub max_bids: u32,
+    /// Marketplace penalty when the auction was created, so later changes do not reach committed bidders.
+    pub unrevealed_penalty: u16,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct SealedBid {
+    pub commitment: [u8; 32],
+    pub deposit: Balance,
+    pub revealed: Option<Balance>,
+}
+
+/// Lists of market items kept in storage so they can be paged through without loading them all.
+#[derive(Encode, Decode, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub enum ItemIndex {
+    // Every registered item
+    All,
+    // Registered items of a collection
+    Collection(AccountId),
+    // Active listings of a seller
+    Seller(AccountId),
+    // Every active listing
+    Active,
+    // Tokens held in custody by the marketplace
+    Escrowed,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct Offer {
+    pub amount: Balance,
+    pub expiry: Timestamp,
+}
+
+#[derive(Encode, Decode, Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "std",
+    derive(scale_info::TypeInfo, ink::storage::traits::StorageLayout)
+)]
+pub struct CollectionOffer {
+    pub id: u64,
+    pub offerer: AccountId,
+    pub price: Balance,
+    pub quantity: u32,
+    pub expiry: Timestamp,
+}
+
 #[derive(Encode, Decode, Debug)]
 #[cfg_attr(
     feature = "std",
@@ -161,6 +492,8 @@ pub struct Collection {
     pub ipfs: String,
     pub creator: Option<AccountId>,
     pub royalty: u16,
+    /// Inactive collections accept no new items, listings or offers.
+    pub active: bool,
 }
 
 impl Default for Collection {
@@ -171,6 +504,7 @@ impl Default for Collection {
             ipfs: Default::default(),
             creator: None,
             royalty: 0,
+            active: false,
         }
     }
 }
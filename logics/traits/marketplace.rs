@@ -2,6 +2,13 @@ use crate::impls::marketplace::types::{
     MarketplaceError,
     Collection,
     AuctionItem,
+    Offer,
+    CollectionOffer,
+    SealedBid,
+    Bundle,
+    ItemIndex,
+    ItemStatus,
+    ListingHealth,
 };
 use ink::primitives::Hash;
 use ink::prelude::vec::Vec;
@@ -18,8 +25,14 @@ use openbrush::{
 
 #[openbrush::trait_definition]
 pub trait NFTMarketplace {
-    //#[ink(message)]
-    //fn create_collection(&mut self, name: String, symbol: String, collection_hash: String, royalty: u16 ) -> Result<AccountId, MarketplaceError>;
+    /// Deploys a new `Token` collection from the stored contract hash and registers it with
+    /// the caller as creator and owner. Returns the address of the new collection.
+    #[ink(message)]
+    fn create_collection(&mut self, name: String, symbol: String, collection_hash: String, royalty: u16 ) -> Result<AccountId, MarketplaceError>;
+
+    /// Collections the creator deployed through `create_collection` that are still registered.
+    #[ink(message)]
+    fn get_creator_collections(&self, creator: AccountId) -> Vec<AccountId>;
 
     #[ink(message)]
     fn add_collection(&mut self, address: AccountId, name: String, symbol: String, collection_hash: String, royalty: u16 ) -> Result<(), MarketplaceError>;
@@ -27,9 +40,32 @@ pub trait NFTMarketplace {
     #[ink(message)]
     fn get_collection(&self, address: AccountId) -> Option<Collection>;
 
+    /// Stops new items, listings and offers in the collection. Running listings can still
+    /// be completed or withdrawn. Callable by the collection creator or the marketplace owner.
+    #[ink(message)]
+    fn deactivate_collection(&mut self, address: AccountId) -> Result<(), MarketplaceError>;
+
+    /// Deletes a collection with no registered items, crediting back any open collection offers.
+    #[ink(message)]
+    fn remove_collection(&mut self, address: AccountId) -> Result<(), MarketplaceError>;
+
+    /// Shares royalties between several recipients. Shares are in basis points and must add up
+    /// to 10000. Rounding dust goes to the creator. An empty split pays the creator alone.
+    /// Royalties the collection's `royalty_info` assigns to someone else are not split.
+    /// Callable by the collection creator.
+    #[ink(message)]
+    fn set_royalty_split(&mut self, address: AccountId, split: Vec<(AccountId, u16)>) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_royalty_split(&self, address: AccountId) -> Vec<(AccountId, u16)>;
+
     #[ink(message)]
     fn get_item(&self, address: AccountId,token_id: Id) -> Option<AuctionItem>;
 
+    /// Auctions past their end time are reported as `AwaitingSettlement`.
+    #[ink(message)]
+    fn get_item_status(&self, address: AccountId, token_id: Id) -> Option<ItemStatus>;
+
     #[ink(message)]
     fn get_collection_count(&self) -> u64;
 
@@ -42,11 +78,84 @@ pub trait NFTMarketplace {
     #[ink(message)]
     fn create_market_item(&mut self,address: AccountId, token_id: Id)  -> Result<(), MarketplaceError>;
 
+    /// Unregisters an unlisted item, crediting back any open offers on it.
+    #[ink(message)]
+    fn remove_market_item(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    /// Refreshes the recorded owner from the collection. A listing whose token was moved away
+    /// is cancelled and its bids are credited back. Callable by anyone.
+    #[ink(message)]
+    fn sync_owner(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    /// Prices the listing in the PSP22 `currency` when set, or in the native currency otherwise.
+    /// A listing with an `expiry` can no longer be bought once that time has passed.
+    #[ink(message)]
+    fn create_direct_sale(&mut self,address: AccountId, token_id: Id, price: Balance, currency: Option<AccountId>,
+        expiry: Option<Timestamp>) -> Result<(), MarketplaceError>;
+
+    /// Bids of at least `buy_now` end the auction immediately. The auction only settles
+    /// automatically once the highest bid reaches `reserve_price`. Auctions priced in a PSP22
+    /// `currency` take bids through `make_token_bid`.
+    #[ink(message)]
+    fn create_auction(&mut self,address: AccountId, token_id: Id, buy_now: Option<Balance>, min_bid: Balance,
+        reserve_price: Balance, reserve_hidden: bool, duration: Timestamp, currency: Option<AccountId>) -> Result<(), MarketplaceError>;
+
+    /// Bids within `extension_window` of the end push the end back to at least
+    /// `extension_length` after the bid, never beyond `max_end_time`.
+    /// Not available for sealed auctions.
+    #[ink(message)]
+    fn set_auction_extension(&mut self, address: AccountId, token_id: Id, extension_window: Timestamp,
+        extension_length: Timestamp, max_end_time: Option<Timestamp>) -> Result<(), MarketplaceError>;
+
+    /// Changes the price of a direct sale without taking it off the market.
+    #[ink(message)]
+    fn update_direct_sale_price(&mut self, address: AccountId, token_id: Id, price: Balance) -> Result<(), MarketplaceError>;
+
+    /// Changes the terms of a running auction. Refused with `AuctionHasBids` once anyone has bid.
+    #[ink(message)]
+    fn update_auction_params(&mut self, address: AccountId, token_id: Id, buy_now: Option<Balance>, min_bid: Balance,
+        bid_end_time: Timestamp) -> Result<(), MarketplaceError>;
+
+    /// Lists the token in a sealed-bid auction. Bids are committed for `commit_duration`
+    /// and revealed during the following `reveal_duration`. With `vickrey` the winner pays
+    /// the second highest bid instead of their own. At most `max_bids` bids can be committed,
+    /// up to `MAX_SEALED_BIDS`.
+    #[ink(message)]
+    fn create_sealed_auction(&mut self, address: AccountId, token_id: Id, min_bid: Balance,
+        commit_duration: Timestamp, reveal_duration: Timestamp, vickrey: bool, max_bids: u32) -> Result<(), MarketplaceError>;
+
+    /// Commits to a hidden bid. The transferred deposit must cover the bid that will be revealed.
+    #[ink(message,payable)]
+    fn commit_bid(&mut self, address: AccountId, token_id: Id, commitment: [u8; 32]) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn reveal_bid(&mut self, address: AccountId, token_id: Id, amount: Balance, salt: [u8; 32]) -> Result<(), MarketplaceError>;
+
+    /// Commitment expected by `commit_bid` for the given bid.
+    #[ink(message)]
+    fn compute_bid_commitment(&self, bidder: AccountId, amount: Balance, salt: [u8; 32]) -> [u8; 32];
+
+    #[ink(message)]
+    fn get_sealed_bid(&self, address: AccountId, token_id: Id, bidder: AccountId) -> Option<SealedBid>;
+
+    /// Share of the deposit, in basis points, forfeited to the seller by bidders that never reveal.
+    /// Sealed auctions keep the penalty in force when they were created.
+    #[ink(message)]
+    fn set_unrevealed_penalty(&mut self, penalty: u16) -> Result<(), MarketplaceError>;
+
     #[ink(message)]
-    fn create_direct_sale(&mut self,address: AccountId, token_id: Id, price: Balance) -> Result<(), MarketplaceError>;
+    fn get_unrevealed_penalty(&self) -> u16;
 
+    /// Lists the token at a price that decays from `start_price` to `end_price` over `duration`.
+    /// A non-zero `step` drops the price in steps of `step` milliseconds instead of linearly.
     #[ink(message)]
-    fn create_auction(&mut self,address: AccountId, token_id: Id, price: Balance, min_bid: Balance, duration: Timestamp) -> Result<(), MarketplaceError>;
+    fn create_dutch_auction(&mut self, address: AccountId, token_id: Id, start_price: Balance, end_price: Balance,
+        start_time: Timestamp, duration: Timestamp, step: Timestamp) -> Result<(), MarketplaceError>;
+
+    /// `None` when the item cannot be bought right now, such as an expired listing or a Dutch
+    /// auction that has not started yet.
+    #[ink(message)]
+    fn get_current_price(&self, address: AccountId, token_id: Id) -> Option<Balance>;
 
     #[ink(message,payable)]
     fn close_direct_sale(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
@@ -54,26 +163,209 @@ pub trait NFTMarketplace {
     #[ink(message)]
     fn withdraw_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
 
+    /// Returns an expired direct sale to its seller. Callable by anyone.
+    #[ink(message)]
+    fn clear_expired_listing(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn is_listing_expired(&self, address: AccountId, token_id: Id) -> bool;
+
+    /// Whether the listing can still be filled, and if not, why.
+    #[ink(message)]
+    fn listing_health(&self, address: AccountId, token_id: Id) -> ListingHealth;
+
     #[ink(message,payable)]
     fn make_bid(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
 
+    /// Bids `amount` of the auction's PSP22 currency, pulled from the caller's allowance.
+    #[ink(message)]
+    fn make_token_bid(&mut self, address: AccountId, token_id: Id, amount: Balance) -> Result<(), MarketplaceError>;
+
+    /// Delivers an ended auction to its highest bidder. Anyone may call it and the caller
+    /// earns the keeper reward. Auctions without a winner, or whose reserve was not met by the
+    /// end of the grace period, are closed: bids and sealed deposits are released and the token
+    /// goes back to the seller. Closing an auction pays no keeper reward, as no fee is taken.
     #[ink(message)]
     fn settle_auction(&mut self,address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
 
+    /// Share of the market fee, in basis points, paid to whoever settles an auction.
+    #[ink(message)]
+    fn set_keeper_reward(&mut self, reward: u16) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_keeper_reward(&self) -> u16;
+
+    /// Auctions within the given page of active items that are ready to settle, including
+    /// those that will close without a sale and so earn no keeper reward.
+    #[ink(message)]
+    fn get_settleable_auctions(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    /// Lets the seller sell to the highest bidder of an ended auction whose reserve was not met.
+    #[ink(message)]
+    fn accept_highest_bid(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    /// Lets the seller refund the highest bidder of an ended auction whose reserve was not met and take the token back.
+    #[ink(message)]
+    fn decline_highest_bid(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    /// How long the seller has to accept or decline a bid below the reserve. Once it passes,
+    /// anyone can settle the auction, which refunds the bidder and returns the token.
+    #[ink(message)]
+    fn set_reserve_grace_period(&mut self, period: Timestamp) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_reserve_grace_period(&self) -> Timestamp;
+
+    #[ink(message)]
+    fn is_reserve_met(&self, address: AccountId, token_id: Id) -> bool;
+
     #[ink(message)]
     fn get_fee_recipient(&self) -> AccountId;
 
+    /// Market fee in basis points. Together with the maximum royalty it cannot exceed 10000.
     #[ink(message)]
     fn set_marketplace_fee(&mut self, fee: u16) -> Result<(), MarketplaceError>;
 
     #[ink(message)]
     fn get_marketplace_fee(&self) -> u16;
 
+    /// Highest royalty, in basis points, paid out on a sale whatever the collection reports.
+    /// Together with the market fee it cannot exceed 10000.
+    #[ink(message)]
+    fn set_max_royalty(&mut self, max_royalty: u16) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_max_royalty(&self) -> u16;
+
     #[ink(message)]
     fn get_item_count(&self) -> u64;
 
+    /// Every registered item, in pages of at most `MAX_PAGE_SIZE` entries.
+    #[ink(message)]
+    fn get_market_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    #[ink(message)]
+    fn get_collection_items(&self, address: AccountId, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    /// Items the seller currently has on sale, including bundled ones.
+    #[ink(message)]
+    fn get_seller_items(&self, seller: AccountId, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    #[ink(message)]
+    fn get_active_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    /// Tokens currently held in custody by the marketplace for active listings.
+    #[ink(message)]
+    fn get_escrowed_items(&self, offset: u64, limit: u64) -> Vec<(AccountId,Id)>;
+
+    /// Number of entries in `index`, for paging through it.
+    #[ink(message)]
+    fn get_index_length(&self, index: ItemIndex) -> u64;
+
+    /// Escrows the transferred value as an offer on a registered item, listed or not. Expired
+    /// offers are dropped and credited back. Once the item has `MAX_OFFERS_PER_TOKEN` offers, a
+    /// new one must beat the lowest, which is evicted and credited back.
+    #[ink(message,payable)]
+    fn make_offer(&mut self, address: AccountId, token_id: Id, expiry: Timestamp) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn cancel_offer(&mut self, address: AccountId, token_id: Id) -> Result<(), MarketplaceError>;
+
+    /// Sells an unlisted item to `offerer`. The marketplace must be approved for the token.
+    #[ink(message)]
+    fn accept_offer(&mut self, address: AccountId, token_id: Id, offerer: AccountId) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_offer(&self, address: AccountId, token_id: Id, offerer: AccountId) -> Option<Offer>;
+
+    #[ink(message)]
+    fn get_offers(&self, address: AccountId, token_id: Id, offset: u64, limit: u64) -> Vec<(AccountId, Offer)>;
+
+    /// Escrows `price * quantity` to buy any `quantity` tokens of a registered collection at `price` each.
+    /// Expired offers are dropped and credited back. Once the book is full, a new offer must beat
+    /// the lowest one, which is evicted and credited back.
+    #[ink(message,payable)]
+    fn make_collection_offer(&mut self, address: AccountId, price: Balance, quantity: u32, expiry: Timestamp) -> Result<u64, MarketplaceError>;
+
+    #[ink(message)]
+    fn cancel_collection_offer(&mut self, address: AccountId, offer_id: u64) -> Result<(), MarketplaceError>;
+
+    /// Sells the caller's token into the collection offer `offer_id`. Fails if that offer was
+    /// cancelled, evicted or has expired.
+    #[ink(message)]
+    fn fill_collection_offer(&mut self, address: AccountId, token_id: Id, offer_id: u64) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_collection_offers(&self, address: AccountId) -> Vec<CollectionOffer>;
+
+    /// Pays out the caller's outbid refunds and sale proceeds.
+    #[ink(message)]
+    fn withdraw_funds(&mut self) -> Result<Balance, MarketplaceError>;
+
+    #[ink(message)]
+    fn get_pending_withdrawal(&self, account: AccountId) -> Balance;
+
+    /// Sum of all balances the marketplace owes to accounts.
+    #[ink(message)]
+    fn get_total_liabilities(&self) -> Balance;
+
+    /// Pays out the caller's refunds and proceeds held in the PSP22 `currency`.
+    #[ink(message)]
+    fn withdraw_token_funds(&mut self, currency: AccountId) -> Result<Balance, MarketplaceError>;
+
+    #[ink(message)]
+    fn get_pending_token_withdrawal(&self, account: AccountId, currency: AccountId) -> Balance;
+
+    /// Sum of all `currency` balances the marketplace owes to accounts.
+    #[ink(message)]
+    fn get_token_liabilities(&self, currency: AccountId) -> Balance;
+
+    #[ink(message)]
+    fn add_currency(&mut self, currency: AccountId) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn remove_currency(&mut self, currency: AccountId) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn get_accepted_currencies(&self) -> Vec<AccountId>;
+
+    /// Registers (if needed) and lists every `(collection, token, price)` entry. With `atomic`
+    /// the first failure reverts the whole batch, otherwise each entry reports its own result.
+    #[ink(message)]
+    fn batch_create_direct_sale(&mut self, items: Vec<(AccountId, Id, Balance)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError>;
+
+    #[ink(message)]
+    fn batch_withdraw(&mut self, items: Vec<(AccountId, Id)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError>;
+
+    /// Buys every listed entry out of the transferred value and refunds whatever is left.
+    #[ink(message,payable)]
+    fn batch_buy(&mut self, items: Vec<(AccountId, Id)>, atomic: bool)
+    -> Result<Vec<Result<(), MarketplaceError>>, MarketplaceError>;
+
+    /// Lists registered tokens, possibly from several collections, to be sold together at `price`.
+    #[ink(message)]
+    fn create_bundle(&mut self, items: Vec<(AccountId, Id)>, price: Balance) -> Result<u64, MarketplaceError>;
+
+    #[ink(message)]
+    fn create_bundle_auction(&mut self, items: Vec<(AccountId, Id)>, min_bid: Balance, duration: Timestamp) -> Result<u64, MarketplaceError>;
+
+    #[ink(message,payable)]
+    fn buy_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError>;
+
+    #[ink(message,payable)]
+    fn bid_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError>;
+
+    /// Delivers every token of an ended bundle auction to the highest bidder.
+    #[ink(message)]
+    fn settle_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError>;
+
+    #[ink(message)]
+    fn cancel_bundle(&mut self, bundle_id: u64) -> Result<(), MarketplaceError>;
+
     #[ink(message)]
-    fn get_all_market_items(&self) -> Vec<(AccountId,Id)>;
+    fn get_bundle(&self, bundle_id: u64) -> Option<Bundle>;
 
     #[ink(message)]
     fn get_timestamp(&self) ->Timestamp;